@@ -24,7 +24,7 @@ mod download {
         path::{Path, PathBuf},
     };
 
-    static VOICES: &'static [&'static str; 9] = &[
+    static VOICES: &'static [&'static str; 22] = &[
         "af_heart",
         "af_bella",
         "af_nicole",
@@ -34,6 +34,19 @@ mod download {
         "am_adam",
         "bm_daniel",
         "am_fenrir",
+        "ef_dora",
+        "em_alex",
+        "ff_siwis",
+        "hf_alpha",
+        "hm_omega",
+        "if_sara",
+        "im_nicola",
+        "jf_alpha",
+        "jm_kumo",
+        "pf_dora",
+        "pm_alex",
+        "zf_xiaobei",
+        "zm_yunjian",
     ];
 
     /// Download all data needed for building speakoro, if necessary.
@@ -82,6 +95,16 @@ mod download {
             work_queue.push((url, target_path));
         }
 
+        // => config with the vocabulary, to test the tokenizer against
+        let target_path = data_dir.join("config.json");
+
+        if !target_path.exists() {
+            work_queue.push((
+                "https://hf.co/hexgrad/Kokoro-82M/resolve/main/config.json?download=true".to_owned(),
+                target_path,
+            ));
+        }
+
         if !work_queue.is_empty() {
             work_queue
                 .iter()
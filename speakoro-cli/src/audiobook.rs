@@ -0,0 +1,241 @@
+//! Audiobooks with one audio file per chapter.
+
+use std::{fs::File, io::BufReader, path::Path, str::FromStr, time::Duration};
+
+use anyhow::{Context, Result, ensure};
+use clap::{Arg, ArgMatches, Command};
+use speakoro::{
+    Kokoro,
+    book::{self, BookFormat, Chapter, Track, epub},
+    text::markup::{self, CodeBlocks},
+};
+use strum::VariantNames;
+
+use crate::synth::{self, PostProcessing, SynthOptions};
+
+/// Maximum length of a chapter title in a file name.
+static MAX_FILE_TITLE_LEN: usize = 60;
+
+pub fn command() -> Command {
+    Command::new("audiobook")
+        .about("Convert a book into one audio file per chapter, with an M3U playlist and a CUE sheet. Chapters that were already written are skipped, so that an interrupted conversion can be resumed.")
+        .arg(
+            Arg::new("book")
+                .index(1)
+                .required(true)
+                .help("Path to the book, as plain text, Markdown or EPUB. Chapters of an EPUB follow the order of its spine, and are titled by its table of contents."),
+        )
+        .arg(
+            Arg::new("out-dir")
+                .short('o')
+                .long("out-dir")
+                .required(true)
+                .help("Set the directory the chapters, playlist and CUE sheet are written to."),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(BookFormat::VARIANTS.to_owned())
+                .help("Set how chapters are detected. In plain text, chapters start with lines like 'Chapter 1' or 'Epilogue', in Markdown with the highest level of heading. Defaults to markdown for files ending in .md, otherwise plain. Ignored for EPUB files."),
+        )
+        .arg(synth::code_blocks_arg())
+        .arg(
+            Arg::new("title")
+                .long("title")
+                .help("Set the title of the book, used for the playlist and CUE sheet. Defaults to the title of an EPUB, or the file name of the book."),
+        )
+        .arg(
+            Arg::new("skip-front-matter")
+                .long("skip-front-matter")
+                .action(clap::ArgAction::SetTrue)
+                .help("If set, the cover, title page, table of contents and similar front matter of an EPUB are not read."),
+        )
+        .arg(
+            Arg::new("overwrite")
+                .long("overwrite")
+                .action(clap::ArgAction::SetTrue)
+                .help("If set, chapters are synthesized even if their output already exists."),
+        )
+        .args(synth::args())
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    // CLI arguments
+    let book_path = Path::new(matches.get_one::<String>("book").unwrap());
+    let out_dir = Path::new(matches.get_one::<String>("out-dir").unwrap());
+    let has_extension = |extensions: &[&str]| {
+        book_path
+            .extension()
+            .is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+    };
+    let format = match matches.get_one::<String>("format") {
+        Some(format) => BookFormat::from_str(format).unwrap(),
+        None if has_extension(&["md", "markdown"]) => BookFormat::Markdown,
+        None => BookFormat::Plain,
+    };
+    let code_blocks =
+        CodeBlocks::from_str(matches.get_one::<String>("code-blocks").unwrap()).unwrap();
+    let skip_front_matter = matches.get_flag("skip-front-matter");
+    let overwrite = matches.get_flag("overwrite");
+
+    let options = SynthOptions::from_matches(matches)?;
+    let post_processing = PostProcessing::from_matches(matches);
+
+    let (book_title, chapters) = if has_extension(&["epub"]) {
+        let file = File::open(book_path)
+            .with_context(|| format!("Reading book from {book_path:?} failed"))?;
+        let epub = epub::read_epub(BufReader::new(file), skip_front_matter)
+            .with_context(|| format!("Reading EPUB {book_path:?} failed"))?;
+        (epub.title, epub.chapters)
+    } else {
+        let text = std::fs::read_to_string(book_path)
+            .with_context(|| format!("Reading book from {book_path:?} failed"))?;
+        let chapters = book::split_chapters(&text, format);
+
+        // Chapters of Markdown are read without their markup
+        let chapters = match format {
+            BookFormat::Markdown => chapters
+                .into_iter()
+                .map(|chapter| Chapter {
+                    text: markup::markdown_to_text(&chapter.text, code_blocks),
+                    ..chapter
+                })
+                .collect(),
+            BookFormat::Plain => chapters,
+        };
+        (None, chapters)
+    };
+    let title = match matches.get_one::<String>("title") {
+        Some(title) => title.clone(),
+        None => book_title.unwrap_or_else(|| {
+            book_path.file_stem().map_or_else(
+                || "Audiobook".to_owned(),
+                |stem| stem.to_string_lossy().into_owned(),
+            )
+        }),
+    };
+    ensure!(!chapters.is_empty(), "The book {book_path:?} is empty");
+
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("Creating output directory {out_dir:?} failed"))?;
+
+    // inference
+    let phonemizers = synth::phonemizers(matches)?;
+    let kokoro = Kokoro::new()?;
+
+    let mut tracks = Vec::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        let chapter_title = chapter_title(chapter, i);
+        let file = chapter_file_name(chapter, i, chapters.len());
+        let path = out_dir.join(&file);
+        let progress = format!("[{}/{}] {chapter_title}", i + 1, chapters.len());
+
+        let duration = if !overwrite && path.exists() {
+            eprintln!("{progress}: skipped, output exists");
+            synth::wav_duration(&path)?
+        } else {
+            // The title is read as a paragraph of its own
+            let text = match &chapter.title {
+                Some(title) => format!("{title}\n\n{}", chapter.text),
+                None => chapter.text.clone(),
+            };
+
+            let synthesis = synth::synthesize_with_progress(
+                &kokoro,
+                Some(&phonemizers),
+                &text,
+                &options,
+                |done, total| eprint!("\r{progress}: sentence {done}/{total}"),
+            )
+            .with_context(|| format!("Synthesizing chapter '{chapter_title}' failed"))?;
+            let audio = post_processing.apply(synthesis.audio)?;
+            let duration = audio.len() as f32 / speakoro::SAMPLE_RATE as f32;
+            synth::write_wav_atomically(audio, &path)?;
+
+            eprintln!("\r{progress}: {}", format_duration(duration));
+            duration
+        };
+
+        tracks.push(Track {
+            title: chapter_title,
+            file,
+            duration: Duration::from_secs_f32(duration),
+        });
+    }
+
+    let playlist_stem = sanitize_file_name(&title);
+    let playlist_stem = if playlist_stem.is_empty() {
+        "audiobook".to_owned()
+    } else {
+        playlist_stem
+    };
+    write(
+        &out_dir.join(format!("{playlist_stem}.m3u")),
+        book::to_m3u(&tracks),
+    )?;
+    write(
+        &out_dir.join(format!("{playlist_stem}.cue")),
+        book::to_cue(&title, &tracks),
+    )?;
+
+    let total: Duration = tracks.iter().map(|track| track.duration).sum();
+    eprintln!(
+        "Done, {} chapters with a total length of {}",
+        tracks.len(),
+        format_duration(total.as_secs_f32())
+    );
+
+    Ok(())
+}
+
+/// Title of a chapter, or its number if it has none.
+fn chapter_title(chapter: &Chapter, index: usize) -> String {
+    chapter
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Part {}", index + 1))
+}
+
+/// File name of a chapter, like "03 - The Storm.wav". Numbers are padded so that the files sort in order.
+fn chapter_file_name(chapter: &Chapter, index: usize, total: usize) -> String {
+    let width = total.to_string().len().max(2);
+    let title = chapter
+        .title
+        .as_deref()
+        .map(sanitize_file_name)
+        .unwrap_or_default();
+
+    if title.is_empty() {
+        format!("{:0width$}.wav", index + 1)
+    } else {
+        format!("{:0width$} - {title}.wav", index + 1)
+    }
+}
+
+/// Remove characters that aren't allowed in file names on common file systems.
+fn sanitize_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| {
+            !c.is_control() && !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+        })
+        .take(MAX_FILE_TITLE_LEN)
+        .collect();
+
+    name.trim().trim_end_matches('.').to_owned()
+}
+
+/// Format seconds like 1:02:03.
+fn format_duration(seconds: f32) -> String {
+    let seconds = seconds.round() as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn write(path: &Path, contents: String) -> Result<()> {
+    std::fs::write(path, contents).with_context(|| format!("Writing {path:?} failed"))
+}
@@ -0,0 +1,318 @@
+//! Batch synthesis of many entries from a manifest, with the model loaded only once.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, mpsc},
+    thread,
+};
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use clap::{Arg, ArgMatches, Command, value_parser};
+use serde::{Deserialize, Serialize};
+use speakoro::{Kokoro, MAX_SPEED, MIN_SPEED, SAMPLE_RATE, VoiceBlend, phonemizer::Phonemizers};
+
+use crate::synth::{self, PostProcessing, SynthOptions};
+
+/// A single entry of the manifest.
+#[derive(Debug, Deserialize)]
+struct Job {
+    id: String,
+    text: Option<String>,
+    phonemes: Option<String>,
+    voice: Option<String>,
+    speed: Option<f32>,
+}
+
+/// Outcome of a job, written to the results manifest.
+#[derive(Debug, Serialize)]
+struct JobResult {
+    id: String,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    /// Duration of the generated audio in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Skipped,
+    Error,
+}
+
+pub fn command() -> Command {
+    Command::new("batch")
+        .about("Synthesize all entries of a JSONL or CSV manifest. Every entry needs an id and either text or phonemes, and can set its own voice and speed.")
+        .arg(
+            Arg::new("manifest")
+                .index(1)
+                .required(true)
+                .help("Path to the manifest. Files ending in .csv are read as CSV with a header row, everything else as JSONL."),
+        )
+        .arg(
+            Arg::new("out-dir")
+                .short('o')
+                .long("out-dir")
+                .required(true)
+                .help("Set the directory the audio is written to. Each entry is written to <id>.wav."),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .value_parser(value_parser!(u16).range(1..))
+                .help("Set the number of parallel workers. Defaults to the number of available CPUs."),
+        )
+        .arg(
+            Arg::new("overwrite")
+                .long("overwrite")
+                .action(clap::ArgAction::SetTrue)
+                .help("If set, entries are synthesized even if their output already exists. Otherwise they are skipped, so that an interrupted batch can be resumed."),
+        )
+        .arg(
+            Arg::new("results")
+                .long("results")
+                .help("Set filepath to where the results manifest (JSONL) will be written to. Defaults to results.jsonl in the output directory."),
+        )
+        .args(synth::args())
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    // CLI arguments
+    let manifest = Path::new(matches.get_one::<String>("manifest").unwrap());
+    let out_dir = Path::new(matches.get_one::<String>("out-dir").unwrap());
+    let workers = match matches.get_one::<u16>("jobs") {
+        Some(&jobs) => jobs as usize,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let overwrite = matches.get_flag("overwrite");
+    let results_path = matches
+        .get_one::<String>("results")
+        .map_or_else(|| out_dir.join("results.jsonl"), PathBuf::from);
+
+    let options = SynthOptions::from_matches(matches)?;
+    let post_processing = PostProcessing::from_matches(matches);
+
+    let jobs = read_manifest(manifest)?;
+    validate_ids(&jobs)?;
+
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("Creating output directory {out_dir:?} failed"))?;
+    let mut results_writer = BufWriter::new(
+        File::create(&results_path)
+            .with_context(|| format!("Creating results manifest {results_path:?} failed"))?,
+    );
+
+    let kokoro = Kokoro::new()?;
+
+    // Workers take jobs from a shared queue and report back, the results are written in order of completion
+    let queue = Mutex::new(jobs.iter());
+    let (tx, rx) = mpsc::channel::<JobResult>();
+    let failed = thread::scope(|scope| -> Result<usize> {
+        let handles: Vec<_> = (0..workers.min(jobs.len()))
+            .map(|_| {
+                let tx = tx.clone();
+                let (queue, kokoro) = (&queue, &kokoro);
+                let (options, post_processing) = (&options, &post_processing);
+
+                scope.spawn(move || -> Result<()> {
+                    let phonemizers = synth::phonemizers(matches)?;
+
+                    while let Some(job) = queue.lock().unwrap().next() {
+                        let result = process(
+                            job,
+                            kokoro,
+                            &phonemizers,
+                            options,
+                            post_processing,
+                            out_dir,
+                            overwrite,
+                        );
+                        if tx.send(result).is_err() {
+                            break;
+                        }
+                    }
+
+                    Ok(())
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut failed = 0;
+        for (done, result) in rx.into_iter().enumerate() {
+            eprintln!(
+                "[{}/{}] {}: {}",
+                done + 1,
+                jobs.len(),
+                result.id,
+                match (&result.status, &result.error) {
+                    (Status::Error, Some(error)) => format!("error: {error}"),
+                    (Status::Skipped, _) => "skipped, output exists".to_owned(),
+                    _ => format!("{:.2}s", result.duration.unwrap_or_default()),
+                }
+            );
+            if result.status == Status::Error {
+                failed += 1;
+            }
+
+            serde_json::to_writer(&mut results_writer, &result)?;
+            writeln!(results_writer)?;
+            results_writer.flush()?;
+        }
+
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| anyhow!("Batch worker panicked"))??;
+        }
+
+        Ok(failed)
+    })?;
+
+    if failed > 0 {
+        bail!(
+            "{failed} of {} entries failed, see {results_path:?} for details",
+            jobs.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Synthesize a single job, or skip it if its output already exists.
+fn process(
+    job: &Job,
+    kokoro: &Kokoro,
+    phonemizers: &Phonemizers,
+    options: &SynthOptions,
+    post_processing: &PostProcessing,
+    out_dir: &Path,
+    overwrite: bool,
+) -> JobResult {
+    let path = out_dir.join(format!("{}.wav", job.id));
+
+    let (status, outcome) = if !overwrite && path.exists() {
+        (Status::Skipped, synth::wav_duration(&path))
+    } else {
+        let outcome = synthesize(job, kokoro, phonemizers, options, post_processing, &path);
+        (Status::Ok, outcome)
+    };
+
+    match outcome {
+        Ok(duration) => JobResult {
+            id: job.id.clone(),
+            status,
+            path: Some(path),
+            duration: Some(duration),
+            error: None,
+        },
+        Err(e) => JobResult {
+            id: job.id.clone(),
+            status: Status::Error,
+            path: None,
+            duration: None,
+            error: Some(format!("{e:#}")),
+        },
+    }
+}
+
+/// Synthesize a job and write it to `path`. Returns the duration of the audio in seconds.
+fn synthesize(
+    job: &Job,
+    kokoro: &Kokoro,
+    phonemizers: &Phonemizers,
+    options: &SynthOptions,
+    post_processing: &PostProcessing,
+    path: &Path,
+) -> Result<f32> {
+    let mut options = options.clone();
+    if let Some(voice) = &job.voice {
+        options.voice = VoiceBlend::from_str(voice)?;
+    }
+    if let Some(speed) = job.speed {
+        ensure!(
+            (MIN_SPEED..=MAX_SPEED).contains(&speed),
+            "Speed must be between {MIN_SPEED} and {MAX_SPEED}, got {speed}"
+        );
+        options.speed = speed;
+    }
+
+    let audio = match (&job.text, &job.phonemes) {
+        (Some(text), None) => synth::synthesize(kokoro, Some(phonemizers), text, &options)?,
+        (None, Some(phonemes)) => synth::synthesize(kokoro, None, phonemes, &options)?,
+        _ => bail!("Entry must have either text or phonemes"),
+    };
+    let audio = post_processing.apply(audio.audio)?;
+    let duration = audio.len() as f32 / SAMPLE_RATE as f32;
+
+    synth::write_wav_atomically(audio, path)?;
+
+    Ok(duration)
+}
+
+/// Read jobs from a CSV or JSONL manifest.
+fn read_manifest(path: &Path) -> Result<Vec<Job>> {
+    let is_csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+    let jobs: Vec<Job> = if is_csv {
+        csv::Reader::from_path(path)
+            .with_context(|| format!("Reading manifest {path:?} failed"))?
+            .deserialize()
+            .enumerate()
+            .map(|(i, job)| job.with_context(|| format!("Invalid entry in row {}", i + 1)))
+            .collect::<Result<_>>()?
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Reading manifest {path:?} failed"))?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Invalid entry in line {}", i + 1))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    // Empty CSV fields are treated as missing
+    Ok(jobs
+        .into_iter()
+        .map(|job| Job {
+            text: job.text.filter(|t| !t.is_empty()),
+            phonemes: job.phonemes.filter(|p| !p.is_empty()),
+            voice: job.voice.filter(|v| !v.is_empty()),
+            ..job
+        })
+        .collect())
+}
+
+/// Ensure that ids are unique and usable as file names.
+fn validate_ids(jobs: &[Job]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for job in jobs {
+        ensure!(
+            !job.id.is_empty() && job.id != "." && job.id != ".." && !job.id.contains(['/', '\\']),
+            "Id '{}' can't be used as a file name",
+            job.id
+        );
+        ensure!(
+            seen.insert(&job.id),
+            "Id '{}' is used more than once",
+            job.id
+        );
+    }
+
+    Ok(())
+}
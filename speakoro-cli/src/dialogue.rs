@@ -0,0 +1,110 @@
+//! Rendering of dialogue scripts with multiple speakers.
+
+use std::{fs::File, path::Path};
+
+use anyhow::{Context, Result};
+use clap::{Arg, ArgMatches, Command};
+use serde::Serialize;
+use speakoro::{Kokoro, dialogue::Script, utils};
+
+use crate::synth::{self, PostProcessing, SynthOptions, bounded_f32};
+
+/// Timing of a line in the rendered track, written to the timing manifest.
+#[derive(Debug, Serialize)]
+struct LineTiming<'a> {
+    speaker: &'a str,
+    text: &'a str,
+    /// Start and end in seconds.
+    start: f32,
+    end: f32,
+}
+
+pub fn command() -> Command {
+    Command::new("dialogue")
+        .about("Render a dialogue script with multiple speakers into a single track. Speakers are assigned voices with lines like '@speaker ALICE af_bella pan=-0.3', followed by lines like 'ALICE: Hello!'.")
+        .arg(
+            Arg::new("script")
+                .index(1)
+                .required(true)
+                .help("Path to the dialogue script."),
+        )
+        .arg(
+            Arg::new("out")
+                .short('o')
+                .long("out")
+                .default_value("dialogue.wav")
+                .help("Set filepath to where the audio will be written to. Note that the output format is WAV, in stereo if any speaker is panned."),
+        )
+        .arg(
+            Arg::new("gap")
+                .long("gap")
+                .value_parser(bounded_f32(0.0, 10.0))
+                .help("Set the silence in seconds between lines, overriding the @gap of the script."),
+        )
+        .arg(
+            Arg::new("timings")
+                .long("timings")
+                .help("Set filepath to where a JSON manifest with the start and end of every line will be written to."),
+        )
+        .args(synth::args())
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    // CLI arguments
+    let script_path = Path::new(matches.get_one::<String>("script").unwrap());
+    let out = Path::new(matches.get_one::<String>("out").unwrap());
+    let timings = matches.get_one::<String>("timings");
+
+    let options = SynthOptions::from_matches(matches)?;
+    let post_processing = PostProcessing::from_matches(matches);
+
+    let mut script: Script = std::fs::read_to_string(script_path)
+        .with_context(|| format!("Reading script from {script_path:?} failed"))?
+        .parse()?;
+    if let Some(&gap) = matches.get_one::<f32>("gap") {
+        script.gap = gap;
+    }
+
+    // inference
+    let phonemizers = synth::phonemizers(matches)?;
+    let kokoro = Kokoro::new()?;
+
+    // Post-processing is applied per line, so that normalization evens out the loudness of the voices
+    let dialogue = script.render(|line, speaker| {
+        let options = SynthOptions {
+            voice: speaker.voice.clone().unwrap_or_else(|| options.voice.clone()),
+            speed: speaker.speed.unwrap_or(options.speed),
+            ..options.clone()
+        };
+        let synthesis = synth::synthesize(&kokoro, Some(&phonemizers), &line.text, &options)?;
+
+        post_processing.apply(synthesis.audio)
+    })?;
+
+    if dialogue.is_stereo() {
+        utils::write_stereo_to_wav(dialogue.to_stereo(), out)?;
+    } else {
+        utils::write_to_wav(dialogue.to_mono(), out)?;
+    }
+
+    if let Some(timings) = timings {
+        let lines: Vec<LineTiming> = dialogue
+            .lines
+            .iter()
+            .map(|line| LineTiming {
+                speaker: &line.speaker,
+                text: &line.text,
+                start: utils::samples_to_duration(line.start).as_secs_f32(),
+                end: utils::samples_to_duration(line.end()).as_secs_f32(),
+            })
+            .collect();
+
+        serde_json::to_writer_pretty(
+            File::create(timings)
+                .with_context(|| format!("Creating timing manifest {timings:?} failed"))?,
+            &lines,
+        )?;
+    }
+
+    Ok(())
+}
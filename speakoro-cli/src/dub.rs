@@ -0,0 +1,248 @@
+//! Dubbing: synthesize the cues of a subtitle file into a single track, fitting each cue into its time window.
+
+use std::{fs::File, ops::Range, path::Path, time::Duration};
+
+use anyhow::{Context, Result, ensure};
+use clap::{Arg, ArgMatches, Command};
+use serde::Serialize;
+use speakoro::{
+    Kokoro, MAX_SPEED, MIN_SPEED, SAMPLE_RATE, VoiceBlend,
+    dsp::{stretch, trim_silence},
+    subtitles::{self, Cue},
+    utils,
+};
+
+use crate::synth::{self, PostProcessing, SynthOptions, bounded_f32};
+
+/// Samples quieter than this (in dBFS) at the edges of a synthesized cue are removed.
+static SILENCE_THRESHOLD: f32 = -50.0;
+/// Number of times the speed is adjusted to fit a cue into its window.
+static SPEED_ATTEMPTS: usize = 3;
+
+/// A cue that didn't fit into its time window.
+#[derive(Debug, Serialize)]
+struct Overflow {
+    /// Position of the cue in the subtitle file, starting at 1.
+    cue: usize,
+    text: String,
+    /// Start and end of the time window in seconds.
+    start: f32,
+    end: f32,
+    /// Duration of the synthesized cue in seconds.
+    duration: f32,
+    speed: f32,
+    tempo: f32,
+}
+
+/// A synthesized cue, and how it was made to fit.
+struct Fitted {
+    audio: Vec<f32>,
+    speed: f32,
+    tempo: f32,
+}
+
+pub fn command() -> Command {
+    Command::new("dub")
+        .about("Synthesize the cues of an SRT file into a single track. Cues that are too long for their time window are spoken faster, and time-stretched if that isn't enough.")
+        .arg(
+            Arg::new("subtitles")
+                .index(1)
+                .required(true)
+                .help("Path to the SRT file."),
+        )
+        .arg(
+            Arg::new("out")
+                .short('o')
+                .long("out")
+                .default_value("track.wav")
+                .help("Set filepath to where the track will be written to. Note that the output format is WAV."),
+        )
+        .arg(
+            Arg::new("max-speed")
+                .long("max-speed")
+                .value_parser(bounded_f32(MIN_SPEED, MAX_SPEED))
+                .default_value("1.4")
+                .help("Set the highest speaking rate used to fit a cue into its time window. Must be between 0.5 and 2."),
+        )
+        .arg(
+            Arg::new("max-tempo")
+                .long("max-tempo")
+                .value_parser(bounded_f32(1.0, 4.0))
+                .default_value("1.3")
+                .help("Set how much faster a cue may be time-stretched if it doesn't fit into its time window at the highest speaking rate. Must be between 1 and 4."),
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .help("Set filepath to where a JSON report of the cues that overflowed their time window will be written to."),
+        )
+        .args(synth::args())
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    // CLI arguments
+    let subtitles_path = Path::new(matches.get_one::<String>("subtitles").unwrap());
+    let out = Path::new(matches.get_one::<String>("out").unwrap());
+    let max_speed = *matches.get_one::<f32>("max-speed").unwrap();
+    let max_tempo = *matches.get_one::<f32>("max-tempo").unwrap();
+    let report = matches.get_one::<String>("report");
+
+    let options = SynthOptions::from_matches(matches)?;
+    let post_processing = PostProcessing::from_matches(matches);
+    ensure!(
+        post_processing.tempo.is_none(),
+        "--tempo would move cues away from their time windows and can't be used for dubbing"
+    );
+
+    let cues = subtitles::parse_srt(
+        &std::fs::read_to_string(subtitles_path)
+            .with_context(|| format!("Reading subtitles from {subtitles_path:?} failed"))?,
+    )?;
+
+    // inference
+    let phonemizers = synth::phonemizers(matches)?;
+    let kokoro = Kokoro::new()?;
+
+    let mut track: Vec<f32> = Vec::new();
+    let mut overflows = Vec::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let text = speakable_text(cue);
+        if text.is_empty() {
+            continue;
+        }
+
+        let samples = cue_samples(cue);
+        let window = samples.len();
+
+        let phonemes = synth::phonemize(&phonemizers, &text, &options)?;
+        let fitted = fit(
+            &kokoro,
+            &phonemes,
+            &options.voice,
+            options.speed,
+            max_speed,
+            max_tempo,
+            window,
+        )?;
+
+        if fitted.audio.len() > window {
+            let overflow = Overflow {
+                cue: i + 1,
+                text,
+                start: cue.start.as_secs_f32(),
+                end: cue.end.as_secs_f32(),
+                duration: fitted.audio.len() as f32 / SAMPLE_RATE as f32,
+                speed: fitted.speed,
+                tempo: fitted.tempo,
+            };
+            eprintln!(
+                "Cue {} ({:.2}s - {:.2}s) overflows its window by {:.2}s: {}",
+                overflow.cue,
+                overflow.start,
+                overflow.end,
+                overflow.duration - (overflow.end - overflow.start),
+                overflow.text
+            );
+            overflows.push(overflow);
+        }
+
+        // Overflowing cues overlap whatever comes after them
+        let end = samples.start + fitted.audio.len();
+        if track.len() < end {
+            track.resize(end, 0.0);
+        }
+        track[samples.start..end]
+            .iter_mut()
+            .zip(fitted.audio)
+            .for_each(|(t, s)| *t += s);
+    }
+
+    // The track lasts at least until the last cue ends
+    if let Some(last) = cues.last() {
+        let end = cue_samples(last).end;
+        if track.len() < end {
+            track.resize(end, 0.0);
+        }
+    }
+
+    let track = post_processing.apply(track)?;
+    utils::write_to_wav(track, out)?;
+
+    if let Some(report) = report {
+        serde_json::to_writer_pretty(
+            File::create(report).with_context(|| format!("Creating report {report:?} failed"))?,
+            &overflows,
+        )?;
+    }
+    eprintln!(
+        "{} of {} cues overflowed their time window.",
+        overflows.len(),
+        cues.len()
+    );
+
+    Ok(())
+}
+
+/// Synthesize phonemes so that they fit into `window` samples if possible. Since the duration of the audio
+/// is roughly inversely proportional to the speed, the speed is first adjusted accordingly. If the audio
+/// still doesn't fit at `max_speed`, it is time-stretched by at most `max_tempo`.
+fn fit(
+    kokoro: &Kokoro,
+    phonemes: &str,
+    voice: &VoiceBlend,
+    speed: f32,
+    max_speed: f32,
+    max_tempo: f32,
+    window: usize,
+) -> Result<Fitted> {
+    let window = window.max(1);
+    let synthesize = |speed: f32| -> Result<Vec<f32>> {
+        let audio = kokoro.phonemes2audio(phonemes, voice, speed)?;
+        Ok(trim_silence(&audio, SILENCE_THRESHOLD).to_vec())
+    };
+
+    let mut speed = speed;
+    let mut audio = synthesize(speed)?;
+    for _ in 0..SPEED_ATTEMPTS {
+        if audio.len() <= window || speed >= max_speed {
+            break;
+        }
+        speed = (speed * audio.len() as f32 / window as f32).min(max_speed);
+        audio = synthesize(speed)?;
+    }
+
+    let mut tempo = 1.0;
+    if audio.len() > window {
+        tempo = (audio.len() as f32 / window as f32).min(max_tempo);
+        audio = stretch::time_stretch(&audio, SAMPLE_RATE, tempo)?;
+    }
+
+    Ok(Fitted {
+        audio,
+        speed,
+        tempo,
+    })
+}
+
+/// Samples covered by the time window of a cue.
+fn cue_samples(cue: &Cue) -> Range<usize> {
+    let samples = |time: Duration| (time.as_secs_f64() * SAMPLE_RATE as f64).round() as usize;
+    samples(cue.start)..samples(cue.end)
+}
+
+/// Text of a cue without formatting tags like `<i>` or `{\an8}`, on a single line.
+fn speakable_text(cue: &Cue) -> String {
+    let mut text = String::new();
+    let mut closing = None;
+    for c in cue.text.chars() {
+        match (closing, c) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (None, c) => text.push(c),
+            (Some(end), c) if c == end => closing = None,
+            _ => {}
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
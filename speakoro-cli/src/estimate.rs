@@ -0,0 +1,48 @@
+//! Subcommand estimating the duration of audio without synthesizing it.
+
+use anyhow::Result;
+use clap::{ArgMatches, Command};
+use speakoro::estimate::DurationEstimator;
+
+use crate::{
+    input::{self, Input},
+    synth::{self, PostProcessing, SynthOptions},
+};
+
+pub fn command() -> Command {
+    Command::new("estimate")
+        .about("Estimate how long the audio for a text will be, without running the model. Takes the same arguments as synthesizing the text, and prints the duration in seconds.")
+        .args(input::args())
+        .group(input::group())
+        .args(synth::args())
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let options = SynthOptions::from_matches(matches)?;
+    let post_processing = PostProcessing::from_matches(matches);
+    let Input {
+        text,
+        ssml,
+        is_phonemes,
+    } = input::read(matches, options.language())?;
+
+    let phonemizers = if !is_phonemes {
+        Some(synth::phonemizers(matches)?)
+    } else {
+        None
+    };
+    let estimator = DurationEstimator::default();
+
+    let duration = match &ssml {
+        Some(ssml) => synth::estimate_ssml(&estimator, phonemizers.as_ref(), ssml, &options)?,
+        None => synth::estimate(&estimator, phonemizers.as_ref(), &text, &options)?,
+    };
+    // Only the tempo changes the duration after synthesis
+    let duration = match post_processing.tempo {
+        Some(tempo) => duration.div_f32(tempo),
+        None => duration,
+    };
+    println!("{:.1}", duration.as_secs_f32());
+
+    Ok(())
+}
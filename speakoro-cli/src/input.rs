@@ -0,0 +1,134 @@
+//! Reading the text to synthesize, shared by the main command and `estimate`.
+
+use std::{io::Read, path::Path, str::FromStr};
+
+use anyhow::{Context, Result, ensure};
+use clap::{Arg, ArgGroup, ArgMatches};
+use speakoro::{
+    Language,
+    phonemes::{arpabet, xsampa},
+    ssml::Ssml,
+    text::markup::{self, CodeBlocks, Markup},
+};
+use strum::VariantNames;
+
+use crate::synth;
+
+/// Arguments passing the text and how to read it.
+pub fn args() -> Vec<Arg> {
+    vec![
+        Arg::new("text")
+            .index(1)
+            .help("Pass the text that should be converted to speech, or - to read it from stdin. If the flag --phonemes is set, this will be interpreted as raw phonemes."),
+        Arg::new("file")
+            .short('f')
+            .long("file")
+            .help("Read the text that should be converted to speech from a file, or from stdin if - is passed. Paragraphs are separated by blank lines."),
+        Arg::new("phonemes")
+            .short('p')
+            .long("phonemes")
+            .help("If set, the passed text will be interpreted as phonemes, either IPA or the notation of misaki, e.g. A for eɪ.")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("arpabet")
+            .long("arpabet")
+            .conflicts_with("phonemes")
+            .help("If set, the passed text will be interpreted as ARPAbet phonemes like in CMUdict, with stress digits on vowels. Words are separated by | or punctuation, e.g. \"HH AH0 L OW1 | W ER1 L D\".")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("xsampa")
+            .long("xsampa")
+            .conflicts_with_all(["phonemes", "arpabet"])
+            .help("If set, the passed text will be interpreted as X-SAMPA phonemes, e.g. \"h@\"l@U w3:ld\". Symbols Kokoro doesn't know are reported as errors.")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("markup")
+            .short('m')
+            .long("markup")
+            .value_parser(Markup::VARIANTS.to_owned())
+            .help("Set the markup of the text. Markdown and HTML are read without their markup: headings and list items become sentences, and links are read by their text. SSML supports <break>, <prosody rate>, <voice name>, <say-as>, <phoneme> and <sub>, other elements are read as text with a warning. Defaults to the extension of a file passed with --file, otherwise plain."),
+        synth::code_blocks_arg(),
+    ]
+}
+
+/// Group requiring either the text or a file.
+pub fn group() -> ArgGroup {
+    ArgGroup::new("input").args(["text", "file"]).required(true)
+}
+
+/// Text to synthesize, without the markup of Markdown and HTML.
+pub struct Input {
+    pub text: String,
+    /// The parsed document, if the text is SSML.
+    pub ssml: Option<Ssml>,
+    /// Whether the text is phonemes rather than text.
+    pub is_phonemes: bool,
+}
+
+/// Read the text from the arguments, a file or stdin. SSML is parsed in the given language, and its warnings
+/// are printed.
+pub fn read(matches: &ArgMatches, language: Language) -> Result<Input> {
+    let text = match matches.get_one::<String>("file") {
+        Some(path) if path != "-" => std::fs::read_to_string(path)
+            .with_context(|| format!("Reading text from {path:?} failed"))?,
+        Some(_) => read_stdin()?,
+        None => match matches.get_one::<String>("text").unwrap().as_str() {
+            "-" => read_stdin()?,
+            text => text.to_owned(),
+        },
+    };
+
+    let markup = match matches.get_one::<String>("markup") {
+        Some(markup) => Markup::from_str(markup).unwrap(),
+        None => matches
+            .get_one::<String>("file")
+            .and_then(|path| Path::new(path).extension())
+            .and_then(|ext| Markup::from_extension(&ext.to_string_lossy()))
+            .unwrap_or_default(),
+    };
+    let code_blocks =
+        CodeBlocks::from_str(matches.get_one::<String>("code-blocks").unwrap()).unwrap();
+    let text = match markup {
+        Markup::Markdown => markup::markdown_to_text(&text, code_blocks),
+        Markup::Html => markup::html_to_text(&text, code_blocks),
+        Markup::Plain | Markup::Ssml => text,
+    };
+
+    let is_arpabet = matches.get_flag("arpabet");
+    let is_xsampa = matches.get_flag("xsampa");
+    ensure!(
+        markup != Markup::Ssml || !(is_arpabet || is_xsampa),
+        "SSML can't be combined with --arpabet or --xsampa, use <phoneme> instead"
+    );
+    let text = if is_arpabet {
+        arpabet::to_ipa(&text)?
+    } else if is_xsampa {
+        xsampa::to_ipa(&text)?
+    } else {
+        text
+    };
+
+    let ssml = match markup {
+        Markup::Ssml => {
+            let ssml = Ssml::parse(&text, language)?;
+            for warning in &ssml.warnings {
+                eprintln!("Warning: {warning}");
+            }
+            Some(ssml)
+        }
+        _ => None,
+    };
+
+    Ok(Input {
+        text,
+        ssml,
+        is_phonemes: matches.get_flag("phonemes") || is_arpabet || is_xsampa,
+    })
+}
+
+/// Read all of stdin into a string.
+fn read_stdin() -> Result<String> {
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .with_context(|| "Reading text from stdin failed")?;
+
+    Ok(text)
+}
@@ -0,0 +1,67 @@
+//! Subcommands for pronunciation lexicons.
+
+use anyhow::{Result, bail};
+use clap::{Arg, ArgMatches, Command};
+use speakoro::text::lexicon::Lexicon;
+
+pub fn command() -> Command {
+    Command::new("lexicon")
+        .about("Work with pronunciation lexicons.")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("check")
+                .about("Check that every entry of the lexicons can be parsed and only uses phonemes Kokoro knows.")
+                .arg(
+                    Arg::new("lexicons")
+                        .index(1)
+                        .required(true)
+                        .num_args(1..)
+                        .help("Paths to the lexicons, in TOML or as tab separated values."),
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("check", matches)) => check(matches),
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+/// Report entries with unknown phonemes.
+fn check(matches: &ArgMatches) -> Result<()> {
+    let mut invalid = 0;
+    let mut total = 0;
+    for path in matches.get_many::<String>("lexicons").unwrap() {
+        let lexicon = match Lexicon::load(path) {
+            Ok(lexicon) => lexicon,
+            Err(err) => {
+                eprintln!("{err:#}");
+                invalid += 1;
+                continue;
+            }
+        };
+
+        for entry in lexicon.entries() {
+            total += 1;
+            let unknown = entry.unknown_symbols();
+            if !unknown.is_empty() {
+                let symbols: Vec<String> = unknown.iter().map(|c| format!("'{c}'")).collect();
+                eprintln!(
+                    "{path}: '{}' /{}/ uses unknown phonemes {}",
+                    entry.word,
+                    entry.phonemes,
+                    symbols.join(", ")
+                );
+                invalid += 1;
+            }
+        }
+    }
+
+    if invalid > 0 {
+        bail!("Checking lexicons failed, see the errors above");
+    }
+    println!("Checked {total} entries, all of them are valid");
+
+    Ok(())
+}
@@ -1,36 +1,54 @@
-use std::{path::Path, str::FromStr};
+use std::{fs::File, path::Path, str::FromStr};
 
-use anyhow::Result;
-use clap::{Arg, Command, crate_version};
-use phonemoro::en::phonemizer::EnPhonemizer;
-use speakoro::{Kokoro, KokoroVoice};
+use anyhow::{Context, Result};
+use clap::{Arg, ArgMatches, Command, crate_version};
+use input::Input;
+use serde::Serialize;
+use speakoro::{
+    Kokoro,
+    subtitles::{Cue, SubtitleFormat},
+    utils,
+};
 use strum::VariantNames;
+use synth::{PostProcessing, SynthOptions};
+
+mod audiobook;
+mod batch;
+mod dialogue;
+mod dub;
+mod estimate;
+mod input;
+mod lexicon;
+mod synth;
+
+/// Word timings, written to the timing manifest.
+#[derive(Debug, Serialize)]
+struct WordTimings<'a> {
+    /// Whether words are timed by estimates, as the model doesn't output the durations it predicted.
+    estimated: bool,
+    words: Vec<WordTiming<'a>>,
+}
+
+/// Timing of a word, with its position in the text in characters.
+#[derive(Debug, Serialize)]
+struct WordTiming<'a> {
+    text: &'a str,
+    /// Start and end in seconds.
+    start: f32,
+    end: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    length: Option<usize>,
+}
 
 fn main() -> Result<()> {
     let matches = Command::new("speakoro")
         .version(concat!(crate_version!(), ", uses kokoro model '", env!("SPEAKORO_MODEL_FILE"), "'"))
-        .arg(
-            Arg::new("text")
-                .index(1)
-                .help("Pass the text that should be converted to speech. If the flag --phonemes is set, this will be interpreted as raw phonemes.")
-                .required(true),
-        )
-        .arg(
-            Arg::new("voice")
-                .short('v')
-                .long("voice")
-                // .value_parser(KokoroVoice::from_str)
-                .value_parser(KokoroVoice::VARIANTS.to_owned())
-                .default_value("af_bella")
-                .help("Set which voice should be used to generate audio."),
-        )
-        .arg(
-            Arg::new("phonemes")
-                .short('p')
-                .long("phonemes")
-                .help("If set, the passed text will be interpreted as phonemes.")
-                .action(clap::ArgAction::SetTrue),
-        )
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .args(input::args())
+        .group(input::group())
         .arg(
             Arg::new("out")
                 .short('o')
@@ -38,27 +56,137 @@ fn main() -> Result<()> {
                 .default_value("audio.wav")
                 .help("Set filepath to where the audio will be written to. Note that the output format is WAV."),
         )
+        .arg(
+            Arg::new("subtitles")
+                .long("subtitles")
+                .value_parser(SubtitleFormat::VARIANTS.to_owned())
+                .help("If set, subtitles with one cue per sentence are written next to the audio, e.g. to audio.srt for audio.wav."),
+        )
+        .arg(
+            Arg::new("timings")
+                .long("timings")
+                .help("Set filepath to where a JSON manifest with the start and end of every word and its position in the text will be written to, e.g. to highlight words while they are spoken. Positions count characters of the text as read, i.e. without Markdown or HTML, and are left out for SSML."),
+        )
+        .args(synth::args())
+        .subcommand(audiobook::command())
+        .subcommand(batch::command())
+        .subcommand(dialogue::command())
+        .subcommand(dub::command())
+        .subcommand(estimate::command())
+        .subcommand(lexicon::command())
         .get_matches();
 
-    // CLI arguments
-    let text = matches.get_one::<String>("text").unwrap();
-    let voice = KokoroVoice::from_str(matches.get_one::<String>("voice").unwrap()).unwrap();
+    match matches.subcommand() {
+        Some(("audiobook", matches)) => audiobook::run(matches),
+        Some(("batch", matches)) => batch::run(matches),
+        Some(("dialogue", matches)) => dialogue::run(matches),
+        Some(("dub", matches)) => dub::run(matches),
+        Some(("estimate", matches)) => estimate::run(matches),
+        Some(("lexicon", matches)) => lexicon::run(matches),
+        _ => run(&matches),
+    }
+}
 
-    let is_phonemes = matches.get_flag("phonemes");
+/// Synthesize a single text.
+fn run(matches: &ArgMatches) -> Result<()> {
+    // CLI arguments
     let out = Path::new(matches.get_one::<String>("out").unwrap());
+    let subtitles = matches
+        .get_one::<String>("subtitles")
+        .map(|format| SubtitleFormat::from_str(format).unwrap());
+    let timings = matches.get_one::<String>("timings");
 
-    // inference
-    let text = if !is_phonemes {
-        let phonemizer = EnPhonemizer::new()?;
+    let mut options = SynthOptions::from_matches(matches)?;
+    options.word_timings = timings.is_some();
+    let post_processing = PostProcessing::from_matches(matches);
+    let Input {
+        text,
+        ssml,
+        is_phonemes,
+    } = input::read(matches, options.language())?;
 
-        phonemizer.phonemize(text)?.replace("A", "eɪ") // Unsure why needed, but A isn't pronounced like eI, but lika ah
+    // inference
+    let phonemizers = if !is_phonemes {
+        Some(synth::phonemizers(matches)?)
     } else {
-        text.to_owned()
+        None
     };
-
     let kokoro = Kokoro::new()?;
-    let audio = kokoro.phonemes2audio(&text, voice, 1f32)?;
-    speakoro::utils::write_to_wav(audio, out)?;
+
+    let synthesis = match &ssml {
+        Some(ssml) => synth::synthesize_ssml(&kokoro, phonemizers.as_ref(), ssml, &options)?,
+        None => synth::synthesize(&kokoro, phonemizers.as_ref(), &text, &options)?,
+    };
+    let audio = post_processing.apply(synthesis.audio)?;
+    utils::write_to_wav(audio, out)?;
+
+    if let Some(timings) = timings {
+        if synthesis.estimated_timings {
+            eprintln!(
+                "Warning: The model doesn't output the durations it predicted, words are timed by estimates within each sentence"
+            );
+        }
+
+        // Positions in characters, counted on from the previous word
+        let mut position = (0, 0);
+        let mut char_offset = |byte: usize| {
+            position.1 += text[position.0..byte].chars().count();
+            position.0 = byte;
+            position.1
+        };
+        let seconds = |sample: usize| {
+            utils::samples_to_duration(post_processing.map_sample(sample)).as_secs_f32()
+        };
+
+        let words: Vec<WordTiming> = synthesis
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.words)
+            .map(|word| {
+                let (offset, length) = match ssml {
+                    Some(_) => (None, None),
+                    None => {
+                        let start = char_offset(word.range.start);
+                        (Some(start), Some(char_offset(word.range.end) - start))
+                    }
+                };
+                WordTiming {
+                    text: &word.text,
+                    start: seconds(word.samples.start),
+                    end: seconds(word.samples.end),
+                    offset,
+                    length,
+                }
+            })
+            .collect();
+
+        serde_json::to_writer_pretty(
+            File::create(timings)
+                .with_context(|| format!("Creating timing manifest {timings:?} failed"))?,
+            &WordTimings {
+                estimated: synthesis.estimated_timings,
+                words,
+            },
+        )?;
+    }
+
+    if let Some(format) = subtitles {
+        let cues: Vec<Cue> = synthesis
+            .segments
+            .into_iter()
+            .map(|segment| Cue {
+                start: utils::samples_to_duration(
+                    post_processing.map_sample(segment.samples.start),
+                ),
+                end: utils::samples_to_duration(post_processing.map_sample(segment.samples.end)),
+                text: segment.text,
+            })
+            .collect();
+
+        let path = out.with_extension(format.extension());
+        std::fs::write(&path, format.render(&cues))
+            .with_context(|| format!("Writing subtitles to {path:?} failed"))?;
+    }
 
     Ok(())
 }
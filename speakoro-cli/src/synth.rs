@@ -0,0 +1,575 @@
+//! Synthesis options and pipeline shared by all modes of the CLI.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+    ops::Range,
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, ensure};
+use clap::{Arg, ArgMatches, value_parser};
+use phonemoro::en::phonemizer::EnPhonemizer;
+use speakoro::{
+    Kokoro, KokoroVoice, Language, MAX_SPEED, MIN_SPEED, Phonemizer, SAMPLE_RATE, VoiceBlend,
+    dsp::{loudness::Normalization, stretch},
+    estimate::DurationEstimator,
+    phonemizer::Phonemizers,
+    ssml::{self, Ssml},
+    text::{lexicon::Lexicon, markup::CodeBlocks, overrides},
+    timings,
+    utils,
+};
+use strum::VariantNames;
+
+/// Arguments controlling synthesis and post-processing.
+pub fn args() -> Vec<Arg> {
+    vec![
+        Arg::new("voice")
+            .short('v')
+            .long("voice")
+            .value_parser(VoiceBlend::from_str)
+            .default_value("af_bella")
+            .help(format!(
+                "Set which voice should be used to generate audio. Voices can be blended with weights, e.g. af_bella*0.7+af_nicole*0.3. [possible values: {}]",
+                KokoroVoice::VARIANTS.join(", ")
+            )),
+        Arg::new("accent")
+            .long("accent")
+            .value_parser(Language::VARIANTS.to_owned())
+            .help("Set the language and accent that text is phonemized and normalized in. Defaults to the one of the voice, for blends the one of the voice with the highest weight."),
+        Arg::new("phonemizer")
+            .long("phonemizer")
+            .value_parser(parse_phonemizer_command)
+            .action(clap::ArgAction::Append)
+            .help("Phonemize text of a language with an external command, e.g. es='espeak-ng -q --ipa -v es'. The text is passed on stdin and the phonemes are read from stdout. Only English has a built-in phonemizer, so this is needed to read text with voices of other languages. Can be passed once per language."),
+        Arg::new("speed")
+            .short('s')
+            .long("speed")
+            .value_parser(bounded_f32(MIN_SPEED, MAX_SPEED))
+            .default_value("1")
+            .help("Set the speaking rate of the model, e.g. 1.2 speaks 20% faster. Must be between 0.5 and 2."),
+        Arg::new("sentence-pause")
+            .long("sentence-pause")
+            .value_parser(bounded_f32(0.0, 10.0))
+            .default_value("0.2")
+            .help("Set the silence in seconds that is inserted between sentences."),
+        Arg::new("paragraph-pause")
+            .long("paragraph-pause")
+            .value_parser(bounded_f32(0.0, 10.0))
+            .default_value("0.6")
+            .help("Set the silence in seconds that is inserted between paragraphs, which are separated by blank lines."),
+        Arg::new("no-normalize")
+            .long("no-normalize")
+            .action(clap::ArgAction::SetTrue)
+            .help("If set, numbers, dates, times, currencies, units and abbreviations are passed to the phonemizer as they are, instead of being expanded into words matching the accent of the voice."),
+        Arg::new("lexicon")
+            .long("lexicon")
+            .action(clap::ArgAction::Append)
+            .help("Read pronunciations of words from a lexicon file, in TOML or as tab separated values with the columns word and phonemes. Can be passed multiple times, earlier files take precedence."),
+        Arg::new("pitch")
+            .long("pitch")
+            .value_parser(bounded_f32(-12.0, 12.0))
+            .allow_negative_numbers(true)
+            .help("Shift the pitch of the audio by the given number of semitones, between -12 and 12."),
+        Arg::new("tempo")
+            .long("tempo")
+            .value_parser(bounded_f32(0.25, 4.0))
+            .help("Change the tempo of the audio without changing its pitch, e.g. 1.1 makes it 10% faster. Must be between 0.25 and 4."),
+        Arg::new("loudness")
+            .long("loudness")
+            .value_parser(value_parser!(f32))
+            .allow_negative_numbers(true)
+            .conflicts_with("peak")
+            .help("Normalize the integrated loudness (EBU R128) of the audio to the given value in LUFS, e.g. -16."),
+        Arg::new("true-peak")
+            .long("true-peak")
+            .value_parser(value_parser!(f32))
+            .allow_negative_numbers(true)
+            .default_value("-1")
+            .requires("loudness")
+            .help("Set the maximum true peak in dBTP, which a limiter enforces after loudness normalization."),
+        Arg::new("peak")
+            .long("peak")
+            .value_parser(value_parser!(f32))
+            .allow_negative_numbers(true)
+            .help("Normalize the sample peak of the audio to the given value in dBFS, e.g. -1."),
+    ]
+}
+
+/// Argument selecting how code blocks of Markdown and HTML are read.
+pub fn code_blocks_arg() -> Arg {
+    Arg::new("code-blocks")
+        .long("code-blocks")
+        .value_parser(CodeBlocks::VARIANTS.to_owned())
+        .default_value("announce")
+        .help("Set how code blocks of Markdown and HTML are read: skip leaves them out, announce replaces them with a note on their length, read reads them line by line.")
+}
+
+/// Options for turning text into audio.
+#[derive(Debug, Clone)]
+pub struct SynthOptions {
+    pub voice: VoiceBlend,
+    /// Accent used instead of the one of the voice.
+    pub accent: Option<Language>,
+    pub speed: f32,
+    pub sentence_pause: f32,
+    pub paragraph_pause: f32,
+    /// Whether numbers, dates, units and abbreviations are expanded into words before phonemization.
+    pub normalize: bool,
+    /// Pronunciations applied before phonemization.
+    pub lexicon: Arc<Lexicon>,
+    /// Whether the words of sentences are timed, see [`Segment::words`].
+    pub word_timings: bool,
+}
+
+impl SynthOptions {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut entries = Vec::new();
+        for path in matches.get_many::<String>("lexicon").unwrap_or_default() {
+            entries.extend(Lexicon::load(path)?.entries().iter().cloned());
+        }
+
+        Ok(SynthOptions {
+            voice: matches.get_one::<VoiceBlend>("voice").unwrap().clone(),
+            accent: matches
+                .get_one::<String>("accent")
+                .map(|accent| Language::from_str(accent).unwrap()),
+            speed: *matches.get_one::<f32>("speed").unwrap(),
+            sentence_pause: *matches.get_one::<f32>("sentence-pause").unwrap(),
+            paragraph_pause: *matches.get_one::<f32>("paragraph-pause").unwrap(),
+            normalize: !matches.get_flag("no-normalize"),
+            lexicon: Arc::new(Lexicon::new(entries)?),
+            word_timings: false,
+        })
+    }
+
+    /// Accent that text is phonemized and normalized in.
+    pub fn language(&self) -> Language {
+        self.accent.unwrap_or_else(|| self.voice.language())
+    }
+}
+
+/// Processing applied to the generated audio.
+#[derive(Debug, Clone, Copy)]
+pub struct PostProcessing {
+    pub tempo: Option<f32>,
+    pub pitch: Option<f32>,
+    pub normalization: Option<Normalization>,
+}
+
+impl PostProcessing {
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        let normalization = match matches.get_one::<f32>("loudness") {
+            Some(&target) => Some(Normalization::Loudness {
+                target,
+                true_peak: *matches.get_one::<f32>("true-peak").unwrap(),
+            }),
+            None => matches
+                .get_one::<f32>("peak")
+                .map(|&target| Normalization::Peak { target }),
+        };
+
+        PostProcessing {
+            tempo: matches.get_one::<f32>("tempo").copied(),
+            pitch: matches.get_one::<f32>("pitch").copied(),
+            normalization,
+        }
+    }
+
+    /// Position of a sample of the unprocessed audio in the processed audio.
+    pub fn map_sample(&self, sample: usize) -> usize {
+        match self.tempo {
+            Some(tempo) => (sample as f32 / tempo).round() as usize,
+            None => sample,
+        }
+    }
+
+    pub fn apply(&self, mut audio: Vec<f32>) -> Result<Vec<f32>> {
+        if let Some(tempo) = self.tempo {
+            audio = stretch::time_stretch(&audio, SAMPLE_RATE, tempo)?;
+        }
+        if let Some(pitch) = self.pitch {
+            audio = stretch::pitch_shift(&audio, SAMPLE_RATE, pitch)?;
+        }
+        if let Some(normalization) = self.normalization {
+            normalization.apply(&mut audio, SAMPLE_RATE);
+        }
+
+        Ok(audio)
+    }
+}
+
+/// Audio generated from text.
+pub struct Synthesis {
+    pub audio: Vec<f32>,
+    /// The synthesized sentences, in order.
+    pub segments: Vec<Segment>,
+    /// Whether words were timed by estimates, as the model doesn't output the durations it predicted.
+    pub estimated_timings: bool,
+}
+
+/// A sentence and the range of samples it was synthesized to.
+pub struct Segment {
+    pub text: String,
+    pub samples: Range<usize>,
+    /// The words of the sentence if [`SynthOptions::word_timings`] is set, otherwise none.
+    pub words: Vec<Word>,
+}
+
+/// A word and the range of samples it was spoken in.
+pub struct Word {
+    /// The word without markup.
+    pub text: String,
+    /// Byte range of the word in the synthesized text.
+    pub range: Range<usize>,
+    pub samples: Range<usize>,
+}
+
+/// What text is synthesized as, in order.
+enum Part<'a> {
+    /// Silence of the given seconds.
+    Pause(f32),
+    /// A sentence, as a byte range of the text it is part of, read with the options.
+    Sentence {
+        text: &'a str,
+        range: Range<usize>,
+        options: Cow<'a, SynthOptions>,
+    },
+}
+
+/// Split text into sentences, with pauses between sentences and paragraphs.
+fn text_parts<'a>(text: &'a str, options: Cow<'a, SynthOptions>) -> Vec<Part<'a>> {
+    let mut parts = Vec::new();
+    for (i, paragraph) in speakoro::text::paragraph_spans(text).into_iter().enumerate() {
+        if i > 0 {
+            parts.push(Part::Pause(options.paragraph_pause));
+        }
+
+        let sentences = speakoro::text::sentence_spans(&text[paragraph.clone()]);
+        for (j, sentence) in sentences.into_iter().enumerate() {
+            if j > 0 {
+                parts.push(Part::Pause(options.sentence_pause));
+            }
+            parts.push(Part::Sentence {
+                text,
+                range: paragraph.start + sentence.start..paragraph.start + sentence.end,
+                options: options.clone(),
+            });
+        }
+    }
+
+    parts
+}
+
+/// Split the segments of an SSML document into sentences, each with its voice and speed relative to the
+/// options. Speech following speech is separated by the sentence pause.
+fn ssml_parts<'a>(ssml: &'a Ssml, options: &'a SynthOptions) -> Vec<Part<'a>> {
+    let mut parts = Vec::new();
+    let mut after_speech = false;
+    for segment in &ssml.segments {
+        match segment {
+            ssml::Segment::Speech { text, voice, rate } => {
+                if after_speech {
+                    parts.push(Part::Pause(options.sentence_pause));
+                }
+
+                let options = SynthOptions {
+                    voice: voice.clone().unwrap_or_else(|| options.voice.clone()),
+                    speed: (options.speed * rate).clamp(MIN_SPEED, MAX_SPEED),
+                    ..options.clone()
+                };
+                parts.extend(text_parts(text, Cow::Owned(options)));
+                after_speech = true;
+            }
+            ssml::Segment::Break(seconds) => {
+                parts.push(Part::Pause(*seconds));
+                after_speech = false;
+            }
+        }
+    }
+
+    parts
+}
+
+/// A sentence with its lines joined.
+fn join_lines(sentence: &str) -> String {
+    sentence.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Phonemes of a sentence. If no phonemizers are passed, the sentence is interpreted as phonemes.
+fn sentence_phonemes(
+    phonemizers: Option<&Phonemizers>,
+    sentence: &str,
+    options: &SynthOptions,
+) -> Result<String> {
+    match phonemizers {
+        Some(phonemizers) => phonemize(phonemizers, sentence, options),
+        None => Ok(sentence.to_owned()),
+    }
+}
+
+/// Synthesize text sentence by sentence, with pauses between sentences and paragraphs. If no phonemizers are
+/// passed, the text is interpreted as phonemes.
+pub fn synthesize(
+    kokoro: &Kokoro,
+    phonemizers: Option<&Phonemizers>,
+    text: &str,
+    options: &SynthOptions,
+) -> Result<Synthesis> {
+    synthesize_with_progress(kokoro, phonemizers, text, options, |_, _| {})
+}
+
+/// Like [`synthesize`], but calls `progress` with the number of synthesized and total sentences after each
+/// sentence.
+pub fn synthesize_with_progress(
+    kokoro: &Kokoro,
+    phonemizers: Option<&Phonemizers>,
+    text: &str,
+    options: &SynthOptions,
+    progress: impl FnMut(usize, usize),
+) -> Result<Synthesis> {
+    let parts = text_parts(text, Cow::Borrowed(options));
+    synthesize_parts(kokoro, phonemizers, parts, progress)
+}
+
+/// Synthesize parts in order, calling `progress` with the number of synthesized and total sentences after each
+/// sentence.
+fn synthesize_parts(
+    kokoro: &Kokoro,
+    phonemizers: Option<&Phonemizers>,
+    parts: Vec<Part>,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Synthesis> {
+    let total = parts
+        .iter()
+        .filter(|part| matches!(part, Part::Sentence { .. }))
+        .count();
+
+    let mut audio = Vec::new();
+    let mut segments = Vec::new();
+    let mut estimated_timings = false;
+    for part in parts {
+        let (text, range, options) = match part {
+            Part::Pause(seconds) => {
+                audio.extend(utils::silence(seconds));
+                continue;
+            }
+            Part::Sentence {
+                text,
+                range,
+                options,
+            } => (text, range, options),
+        };
+        let sentence = join_lines(&text[range.clone()]);
+        let phonemes = sentence_phonemes(phonemizers, &sentence, &options)?;
+
+        let start = audio.len();
+        let mut words = Vec::new();
+        if options.word_timings {
+            let timed = kokoro.phonemes2audio_timed(&phonemes, &options.voice, options.speed)?;
+            // Words are counted in the text, so that the phonemizer only runs once per sentence
+            let language = options.language();
+            let spoken_words = |word: &str| match phonemizers {
+                Some(_) => timings::spoken_words(word, language),
+                None => Ok(timings::count_words(word)),
+            };
+            for word in timings::align_words(&text[range.clone()], &timed, spoken_words)? {
+                let word_range = range.start + word.text.start..range.start + word.text.end;
+                words.push(Word {
+                    text: overrides::strip(&text[word_range.clone()]),
+                    range: word_range,
+                    samples: start + word.samples.start..start + word.samples.end,
+                });
+            }
+            estimated_timings |= timed.estimated;
+            audio.extend(timed.audio);
+        } else {
+            audio.extend(kokoro.phonemes2audio(&phonemes, &options.voice, options.speed)?);
+        }
+
+        segments.push(Segment {
+            text: overrides::strip(&sentence),
+            samples: start..audio.len(),
+            words,
+        });
+        progress(segments.len(), total);
+    }
+
+    Ok(Synthesis {
+        audio,
+        segments,
+        estimated_timings,
+    })
+}
+
+/// Estimate the duration of the audio that [`synthesize`] generates for text, without running the model.
+pub fn estimate(
+    estimator: &DurationEstimator,
+    phonemizers: Option<&Phonemizers>,
+    text: &str,
+    options: &SynthOptions,
+) -> Result<Duration> {
+    estimate_parts(estimator, phonemizers, text_parts(text, Cow::Borrowed(options)))
+}
+
+/// Estimate the duration of the audio that [`synthesize_parts`] generates for parts.
+fn estimate_parts(
+    estimator: &DurationEstimator,
+    phonemizers: Option<&Phonemizers>,
+    parts: Vec<Part>,
+) -> Result<Duration> {
+    let mut duration = Duration::ZERO;
+    for part in parts {
+        duration += match part {
+            Part::Pause(seconds) => Duration::from_secs_f32(seconds),
+            Part::Sentence {
+                text,
+                range,
+                options,
+            } => {
+                let phonemes = sentence_phonemes(phonemizers, &join_lines(&text[range]), &options)?;
+                estimator.estimate(&phonemes, &options.voice, options.speed)?
+            }
+        };
+    }
+
+    Ok(duration)
+}
+
+/// Phonemizer running an external command, which reads text from stdin and writes phonemes to stdout.
+#[derive(Debug, Clone)]
+pub struct CommandPhonemizer {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Phonemizer for CommandPhonemizer {
+    fn phonemize(&self, text: &str) -> Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Running phonemizer '{}' failed", self.program))?;
+
+        // Closing stdin by dropping it signals the end of the text
+        child.stdin.take().unwrap().write_all(text.as_bytes())?;
+        let output = child.wait_with_output()?;
+        ensure!(
+            output.status.success(),
+            "Phonemizer '{}' failed with {}",
+            self.program,
+            output.status
+        );
+
+        // Phonemizers like espeak-ng write a line per clause
+        let phonemes = String::from_utf8(output.stdout)
+            .with_context(|| format!("Phonemizer '{}' returned invalid UTF-8", self.program))?;
+        Ok(phonemes.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// Value parser for phonemizer commands like `es='espeak-ng -q --ipa -v es'`.
+fn parse_phonemizer_command(value: &str) -> Result<(Language, CommandPhonemizer), String> {
+    let (language, command) = value
+        .split_once('=')
+        .ok_or("must be a language and a command, separated by '='")?;
+    let language = Language::from_str(language.trim()).map_err(|_| {
+        format!(
+            "unknown language '{language}' [possible values: {}]",
+            Language::VARIANTS.join(", ")
+        )
+    })?;
+
+    let mut parts = command.split_whitespace().map(str::to_owned);
+    let program = parts.next().ok_or("the command must not be empty")?;
+
+    Ok((
+        language,
+        CommandPhonemizer {
+            program,
+            args: parts.collect(),
+        },
+    ))
+}
+
+/// Phonemizers for all languages, with phonemoro for English and the commands passed with `--phonemizer`.
+pub fn phonemizers(matches: &ArgMatches) -> Result<Phonemizers> {
+    let mut phonemizers = Phonemizers::new();
+    phonemizers.insert(Language::EnUs, EnPhonemizer::new()?);
+    for (language, phonemizer) in matches
+        .get_many::<(Language, CommandPhonemizer)>("phonemizer")
+        .unwrap_or_default()
+    {
+        phonemizers.insert(*language, phonemizer.clone());
+    }
+
+    Ok(phonemizers)
+}
+
+/// Phonemize text in the language of the options, applying the lexicon and normalization. Words with an inline
+/// pronunciation or in the lexicon are neither normalized nor phonemized.
+pub fn phonemize(phonemizers: &Phonemizers, text: &str, options: &SynthOptions) -> Result<String> {
+    let language = options.language();
+    let text = options.lexicon.apply(text)?;
+
+    let phonemizer = phonemizers.for_language(language);
+    overrides::phonemize(&text, &phonemizer, language, options.normalize)
+}
+
+/// Synthesize the segments of an SSML document, each with its voice and speed relative to the options. Speech
+/// following speech is separated by the sentence pause.
+pub fn synthesize_ssml(
+    kokoro: &Kokoro,
+    phonemizers: Option<&Phonemizers>,
+    ssml: &Ssml,
+    options: &SynthOptions,
+) -> Result<Synthesis> {
+    synthesize_parts(kokoro, phonemizers, ssml_parts(ssml, options), |_, _| {})
+}
+
+/// Estimate the duration of the audio that [`synthesize_ssml`] generates, without running the model.
+pub fn estimate_ssml(
+    estimator: &DurationEstimator,
+    phonemizers: Option<&Phonemizers>,
+    ssml: &Ssml,
+    options: &SynthOptions,
+) -> Result<Duration> {
+    estimate_parts(estimator, phonemizers, ssml_parts(ssml, options))
+}
+
+/// Write audio to a temporary file first and rename it when done, so that an interrupted write isn't mistaken
+/// for a finished one.
+pub fn write_wav_atomically(audio: Vec<f32>, path: &Path) -> Result<()> {
+    let partial = path.with_extension("wav.part");
+    utils::write_to_wav(audio, &partial)?;
+    std::fs::rename(&partial, path)
+        .with_context(|| format!("Moving {partial:?} to {path:?} failed"))?;
+
+    Ok(())
+}
+
+/// Duration of an existing WAV file in seconds.
+pub fn wav_duration(path: &Path) -> Result<f32> {
+    let reader = hound::WavReader::open(path)
+        .with_context(|| format!("Reading existing output {path:?} failed"))?;
+
+    Ok(reader.duration() as f32 / reader.spec().sample_rate as f32)
+}
+
+/// Value parser for floats within an inclusive range.
+pub fn bounded_f32(min: f32, max: f32) -> impl Fn(&str) -> Result<f32, String> + Clone {
+    move |value| {
+        let value: f32 = value.parse().map_err(|e| format!("{e}"))?;
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(format!("must be between {min} and {max}"))
+        }
+    }
+}
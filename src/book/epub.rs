@@ -0,0 +1,579 @@
+//! Reading chapters from EPUB 2 and 3 files.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use anyhow::{Context, Result, anyhow};
+use quick_xml::{
+    Reader,
+    events::{BytesStart, Event},
+};
+use zip::ZipArchive;
+
+use super::Chapter;
+
+/// Values of `epub:type`, and types of EPUB 2 guide references, which mark a document as front matter.
+static FRONT_MATTER_TYPES: [&str; 11] = [
+    "cover",
+    "titlepage",
+    "title-page",
+    "halftitlepage",
+    "frontmatter",
+    "toc",
+    "copyright-page",
+    "dedication",
+    "imprint",
+    "landmarks",
+    "loi",
+];
+
+/// Elements that start a new paragraph.
+static BLOCK_ELEMENTS: [&str; 22] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "aside",
+    "header",
+    "footer",
+    "blockquote",
+    "li",
+    "dt",
+    "dd",
+    "tr",
+    "td",
+    "th",
+    "br",
+    "hr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+/// Elements whose text isn't read.
+static SKIPPED_ELEMENTS: [&str; 5] = ["head", "script", "style", "rt", "svg"];
+
+/// Contents of an EPUB file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Epub {
+    pub title: Option<String>,
+    /// One chapter per document of the spine, in reading order. Documents without text or title are dropped.
+    pub chapters: Vec<Chapter>,
+}
+
+/// A document of the spine.
+struct SpineItem {
+    path: String,
+    /// Whether the item is marked as non-linear, i.e. as auxiliary content.
+    linear: bool,
+    /// Whether the item is the navigation document of an EPUB 3.
+    nav: bool,
+}
+
+/// Text extracted from an XHTML document.
+#[derive(Debug, Default, PartialEq)]
+struct Document {
+    paragraphs: Vec<String>,
+    /// Index of the first heading in `paragraphs`.
+    heading: Option<usize>,
+    /// All values of `epub:type` attributes on the body and its sections.
+    types: Vec<String>,
+}
+
+/// Read an EPUB, following the order of its spine. Chapter titles are taken from the table of contents, or
+/// from the first heading of a document. If `skip_front_matter` is set, covers, title pages, tables of contents
+/// and similar documents are left out.
+pub fn read_epub<R: Read + Seek>(reader: R, skip_front_matter: bool) -> Result<Epub> {
+    let mut archive = ZipArchive::new(reader).context("EPUB is not a valid ZIP archive")?;
+
+    let container = read_file(&mut archive, "META-INF/container.xml")?;
+    let opf_path = rootfile_path(&container)?;
+    let opf = read_file(&mut archive, &opf_path)?;
+    let package = parse_package(&opf, parent_dir(&opf_path))?;
+
+    // Titles from the table of contents, by document
+    let mut toc_titles = HashMap::new();
+    if let Some(toc_path) = &package.toc {
+        let toc = read_file(&mut archive, toc_path)?;
+        for (href, title) in parse_toc(&toc)? {
+            let path = resolve_href(parent_dir(toc_path), &href);
+            toc_titles.entry(path).or_insert(title);
+        }
+    }
+
+    let mut chapters = Vec::new();
+    for item in &package.spine {
+        let document = parse_xhtml(&read_file(&mut archive, &item.path)?)
+            .with_context(|| format!("Parsing {:?} of the EPUB failed", item.path))?;
+
+        let is_front_matter = !item.linear
+            || item.nav
+            || package.front_matter.contains(&item.path)
+            || document
+                .types
+                .iter()
+                .any(|t| FRONT_MATTER_TYPES.contains(&t.as_str()));
+        if skip_front_matter && is_front_matter {
+            continue;
+        }
+
+        let mut paragraphs = document.paragraphs;
+        let heading = document.heading.map(|i| paragraphs[i].clone());
+        let title = toc_titles.get(&item.path).cloned().or(heading.clone());
+
+        // The title is spoken separately, so it's removed from the text
+        if let (Some(i), Some(title), Some(heading)) = (document.heading, &title, &heading)
+            && title.eq_ignore_ascii_case(heading)
+        {
+            paragraphs.remove(i);
+        }
+
+        let chapter = Chapter {
+            title,
+            text: paragraphs.join("\n\n"),
+        };
+        if chapter.title.is_some() || !chapter.text.is_empty() {
+            chapters.push(chapter);
+        }
+    }
+
+    Ok(Epub {
+        title: package.title,
+        chapters,
+    })
+}
+
+fn read_file<R: Read + Seek>(archive: &mut ZipArchive<R>, path: &str) -> Result<String> {
+    let mut file = archive
+        .by_name(path)
+        .with_context(|| format!("EPUB is missing {path:?}"))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .with_context(|| format!("Reading {path:?} of the EPUB failed"))?;
+
+    Ok(contents)
+}
+
+/// Path of the package document, given the container.
+fn rootfile_path(container: &str) -> Result<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = attribute(&e, "full-path")? {
+                    return Ok(path);
+                }
+            }
+            Event::Eof => return Err(anyhow!("EPUB container doesn't reference a package")),
+            _ => {}
+        }
+    }
+}
+
+/// The parts of the package document needed to read the book. All paths are relative to the archive root.
+struct Package {
+    title: Option<String>,
+    spine: Vec<SpineItem>,
+    /// Table of contents, either the EPUB 3 navigation document or the EPUB 2 NCX.
+    toc: Option<String>,
+    /// Documents that the EPUB 2 guide marks as front matter.
+    front_matter: Vec<String>,
+}
+
+fn parse_package(opf: &str, base: &str) -> Result<Package> {
+    // id -> (path, media type, properties)
+    let mut manifest: HashMap<String, (String, String, String)> = HashMap::new();
+    let mut spine_refs = Vec::new();
+    let mut ncx_id = None;
+    let mut front_matter = Vec::new();
+    let mut title = None;
+    let mut in_title = false;
+
+    let mut reader = Reader::from_str(opf);
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"title" if title.is_none() => in_title = true,
+                b"item" => {
+                    if let (Some(id), Some(href)) = (attribute(&e, "id")?, attribute(&e, "href")?) {
+                        let media_type = attribute(&e, "media-type")?.unwrap_or_default();
+                        let properties = attribute(&e, "properties")?.unwrap_or_default();
+                        manifest.insert(id, (resolve_href(base, &href), media_type, properties));
+                    }
+                }
+                b"spine" => ncx_id = attribute(&e, "toc")?,
+                b"itemref" => {
+                    if let Some(idref) = attribute(&e, "idref")? {
+                        let linear = attribute(&e, "linear")?.is_none_or(|linear| linear != "no");
+                        spine_refs.push((idref, linear));
+                    }
+                }
+                b"reference" => {
+                    if let (Some(kind), Some(href)) =
+                        (attribute(&e, "type")?, attribute(&e, "href")?)
+                        && FRONT_MATTER_TYPES.contains(&kind.as_str())
+                    {
+                        front_matter.push(resolve_href(base, &href));
+                    }
+                }
+                _ => {}
+            },
+            Event::Text(e) if in_title => {
+                let text = e.unescape()?.trim().to_owned();
+                if !text.is_empty() {
+                    title = Some(text);
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"title" => in_title = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let nav = manifest
+        .values()
+        .find(|(_, _, properties)| properties.split_whitespace().any(|p| p == "nav"))
+        .map(|(path, _, _)| path.clone());
+    let toc = nav.clone().or_else(|| {
+        ncx_id
+            .and_then(|id| manifest.get(&id))
+            .map(|(path, _, _)| path.clone())
+    });
+
+    let spine = spine_refs
+        .into_iter()
+        .filter_map(|(idref, linear)| manifest.get(&idref).map(|item| (item, linear)))
+        .filter(|((_, media_type, _), _)| media_type.contains("html"))
+        .map(|((path, _, _), linear)| SpineItem {
+            path: path.clone(),
+            linear,
+            nav: nav.as_ref() == Some(path),
+        })
+        .collect();
+
+    Ok(Package {
+        title,
+        spine,
+        toc,
+        front_matter,
+    })
+}
+
+/// Entries of an EPUB 3 navigation document or an EPUB 2 NCX, as pairs of link and title.
+fn parse_toc(toc: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    // The navigation document can contain other lists, like landmarks
+    let mut nav_depth = 0;
+    let mut in_toc_nav = false;
+    let mut link: Option<(String, String)> = None;
+    let mut label: Option<String> = None;
+
+    let mut reader = Reader::from_str(toc);
+    reader.config_mut().check_end_names = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"nav" => {
+                    nav_depth += 1;
+                    if attribute(&e, "epub:type")?
+                        .is_some_and(|t| t.split_whitespace().any(|t| t == "toc"))
+                    {
+                        in_toc_nav = true;
+                    }
+                }
+                b"a" if in_toc_nav => {
+                    if let Some(href) = attribute(&e, "href")? {
+                        link = Some((href, String::new()));
+                    }
+                }
+                b"text" => label = Some(String::new()),
+                _ => {}
+            },
+            Event::Empty(e) if e.local_name().as_ref() == b"content" => {
+                if let (Some(src), Some(title)) = (attribute(&e, "src")?, label.take()) {
+                    entries.push((src, collapse_whitespace(&title)));
+                }
+            }
+            Event::Text(e) => {
+                let text = e.unescape()?;
+                if let Some((_, title)) = &mut link {
+                    title.push_str(&text);
+                } else if let Some(title) = &mut label {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"nav" => {
+                    nav_depth -= 1;
+                    if nav_depth == 0 {
+                        in_toc_nav = false;
+                    }
+                }
+                b"a" => {
+                    if let Some((href, title)) = link.take() {
+                        entries.push((href, collapse_whitespace(&title)));
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Extract the text of an XHTML document, with one paragraph per block element.
+fn parse_xhtml(xhtml: &str) -> Result<Document> {
+    let mut document = Document::default();
+    let mut paragraph = String::new();
+    let mut skip_depth = 0;
+    let mut heading_depth = 0;
+    let mut in_body = false;
+
+    let mut reader = Reader::from_str(xhtml);
+    reader.config_mut().check_end_names = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = e.local_name();
+                let name = std::str::from_utf8(name.as_ref())?;
+
+                if SKIPPED_ELEMENTS.contains(&name) || skip_depth > 0 {
+                    skip_depth += 1;
+                    continue;
+                }
+                if name == "body" {
+                    in_body = true;
+                }
+                if matches!(name, "body" | "section" | "div" | "article")
+                    && let Some(types) = attribute(&e, "epub:type")?
+                {
+                    document
+                        .types
+                        .extend(types.split_whitespace().map(str::to_owned));
+                }
+                if BLOCK_ELEMENTS.contains(&name) {
+                    end_paragraph(&mut document, &mut paragraph, heading_depth > 0);
+                }
+                if is_heading(name) {
+                    heading_depth += 1;
+                }
+            }
+            Event::Empty(e) => {
+                let name = e.local_name();
+                if skip_depth == 0 && BLOCK_ELEMENTS.contains(&std::str::from_utf8(name.as_ref())?)
+                {
+                    end_paragraph(&mut document, &mut paragraph, heading_depth > 0);
+                }
+            }
+            Event::End(e) => {
+                if skip_depth > 0 {
+                    skip_depth -= 1;
+                    continue;
+                }
+
+                let name = e.local_name();
+                let name = std::str::from_utf8(name.as_ref())?;
+                if BLOCK_ELEMENTS.contains(&name) || name == "body" {
+                    end_paragraph(&mut document, &mut paragraph, heading_depth > 0);
+                }
+                if is_heading(name) {
+                    heading_depth -= 1;
+                }
+            }
+            Event::Text(e) if in_body && skip_depth == 0 => paragraph.push_str(&e.unescape()?),
+            Event::CData(e) if in_body && skip_depth == 0 => {
+                paragraph.push_str(std::str::from_utf8(&e)?)
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    end_paragraph(&mut document, &mut paragraph, false);
+
+    Ok(document)
+}
+
+fn end_paragraph(document: &mut Document, paragraph: &mut String, is_heading: bool) {
+    let text = collapse_whitespace(paragraph);
+    paragraph.clear();
+    if text.is_empty() {
+        return;
+    }
+
+    if is_heading && document.heading.is_none() {
+        document.heading = Some(document.paragraphs.len());
+    }
+    document.paragraphs.push(text);
+}
+
+fn is_heading(name: &str) -> bool {
+    matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>> {
+    Ok(match element.try_get_attribute(name)? {
+        Some(attribute) => Some(attribute.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Directory of a path within the archive, with a trailing slash unless it's the root.
+fn parent_dir(path: &str) -> &str {
+    path.rfind('/').map_or("", |i| &path[..=i])
+}
+
+/// Resolve a link relative to a directory of the archive, dropping any fragment.
+fn resolve_href(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = percent_decode(href);
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in base.split('/').chain(href.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    fn epub(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    fn xhtml(body: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><head><title>Ignored</title></head>{body}</html>"#
+        )
+    }
+
+    #[test]
+    fn read_epub_test() {
+        let container = r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <metadata><dc:title>The Book</dc:title></metadata>
+            <manifest>
+                <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                <item id="cover" href="text/cover.xhtml" media-type="application/xhtml+xml"/>
+                <item id="c1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+                <item id="c2" href="text/c2.xhtml" media-type="application/xhtml+xml"/>
+                <item id="img" href="images/cover.png" media-type="image/png"/>
+            </manifest>
+            <spine><itemref idref="cover"/><itemref idref="nav" linear="no"/><itemref idref="c2"/><itemref idref="c1"/></spine>
+        </package>"#;
+        let nav = xhtml(
+            r#"<body><nav epub:type="toc"><ol>
+                <li><a href="text/chapter%201.xhtml">The <em>First</em></a></li>
+                <li><a href="text/c2.xhtml#start">Second</a></li>
+            </ol></nav></body>"#,
+        );
+        let cover = xhtml(r#"<body epub:type="cover"><p>By Someone</p></body>"#);
+        let c1 = xhtml(
+            "<body><h1>The First</h1><p>It was a\n  dark night &amp; it rained&nbsp;hard.</p><p>The end.</p><script>ignored()</script></body>",
+        );
+        let c2 = xhtml("<body><h2>2</h2><p>Before.<br/>After.</p></body>");
+
+        let book = epub(&[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", opf),
+            ("OEBPS/nav.xhtml", &nav),
+            ("OEBPS/text/cover.xhtml", &cover),
+            ("OEBPS/text/chapter 1.xhtml", &c1),
+            ("OEBPS/text/c2.xhtml", &c2),
+        ]);
+
+        let epub = read_epub(book.clone(), true).unwrap();
+        assert_eq!(epub.title.as_deref(), Some("The Book"));
+        assert_eq!(
+            epub.chapters,
+            vec![
+                Chapter {
+                    title: Some("Second".to_owned()),
+                    text: "2\n\nBefore.\n\nAfter.".to_owned(),
+                },
+                Chapter {
+                    title: Some("The First".to_owned()),
+                    text: "It was a dark night & it rained hard.\n\nThe end.".to_owned(),
+                },
+            ]
+        );
+
+        // Front matter is kept in spine order
+        let epub = read_epub(book, false).unwrap();
+        assert_eq!(epub.chapters.len(), 4);
+        assert_eq!(epub.chapters[0].text, "By Someone");
+        assert_eq!(epub.chapters[1].text, "The First\n\nSecond");
+    }
+
+    #[test]
+    fn resolve_href_test() {
+        assert_eq!(
+            resolve_href("OEBPS/", "text/a.xhtml#x"),
+            "OEBPS/text/a.xhtml"
+        );
+        assert_eq!(
+            resolve_href("OEBPS/toc/", "../a%20b.xhtml"),
+            "OEBPS/a b.xhtml"
+        );
+        assert_eq!(resolve_href("", "./a.xhtml"), "a.xhtml");
+    }
+}
@@ -0,0 +1,264 @@
+//! Long-form text: splitting books into chapters, and playlists for the resulting audio files.
+
+pub mod epub;
+
+use std::{sync::LazyLock, time::Duration};
+
+use regex::Regex;
+use strum::{EnumString, VariantNames};
+
+/// Lines of plain text that start a chapter, like "Chapter 12", "PART IV: The End" or "Epilogue".
+static PLAIN_CHAPTER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^((chapter|part|book)\s+([0-9]+|[ivxlcdm]+|[a-z]+)\b.*|prologue|epilogue|preface|foreword|afterword|introduction)$")
+        .expect("Regex for detecting chapters is not valid.")
+});
+
+/// Markdown headings like "## Title".
+static MARKDOWN_HEADING_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(#{1,6})\s+(.*?)(\s+#+)?\s*$")
+        .expect("Regex for detecting headings is not valid.")
+});
+
+/// Maximum length of a line of plain text that can be a chapter title.
+static MAX_TITLE_LEN: usize = 80;
+
+/// Format of a book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum BookFormat {
+    /// Chapters start with lines like "Chapter 1" that stand on their own.
+    Plain,
+    /// Chapters start with the highest level of heading used in the document.
+    Markdown,
+}
+
+/// A chapter of a book. Text before the first chapter heading becomes a chapter without title.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: Option<String>,
+    /// Text of the chapter, with paragraphs separated by blank lines.
+    pub text: String,
+}
+
+/// Split a book into chapters. Chapters without text are dropped, unless they have a title.
+pub fn split_chapters(book: &str, format: BookFormat) -> Vec<Chapter> {
+    let lines: Vec<&str> = book.lines().collect();
+    let mut chapters = vec![Chapter {
+        title: None,
+        text: String::new(),
+    }];
+
+    match format {
+        BookFormat::Plain => {
+            for (i, line) in lines.iter().enumerate() {
+                let trimmed = line.trim();
+                let stands_alone = (i == 0 || lines[i - 1].trim().is_empty())
+                    && lines.get(i + 1).is_none_or(|next| next.trim().is_empty());
+
+                if stands_alone
+                    && trimmed.len() <= MAX_TITLE_LEN
+                    && PLAIN_CHAPTER_REGEX.is_match(trimmed)
+                {
+                    chapters.push(Chapter {
+                        title: Some(trimmed.to_owned()),
+                        text: String::new(),
+                    });
+                } else {
+                    push_line(chapters.last_mut().unwrap(), line);
+                }
+            }
+        }
+        BookFormat::Markdown => {
+            let headings = markdown_headings(&lines);
+            let chapter_level = headings.iter().map(|(_, level, _)| *level).min();
+
+            let mut headings = headings.into_iter().peekable();
+            for (i, line) in lines.iter().enumerate() {
+                match headings.next_if(|(idx, _, _)| *idx == i) {
+                    Some((_, level, title)) if Some(level) == chapter_level => {
+                        chapters.push(Chapter {
+                            title: Some(title),
+                            text: String::new(),
+                        });
+                    }
+                    // Lower level headings are read as paragraphs of their own
+                    Some((_, _, title)) => {
+                        let chapter = chapters.last_mut().unwrap();
+                        push_line(chapter, "");
+                        push_line(chapter, &title);
+                        push_line(chapter, "");
+                    }
+                    None => push_line(chapters.last_mut().unwrap(), line),
+                }
+            }
+        }
+    }
+
+    chapters
+        .into_iter()
+        .map(|chapter| Chapter {
+            text: chapter.text.trim().to_owned(),
+            ..chapter
+        })
+        .filter(|chapter| chapter.title.is_some() || !chapter.text.is_empty())
+        .collect()
+}
+
+fn push_line(chapter: &mut Chapter, line: &str) {
+    chapter.text.push_str(line);
+    chapter.text.push('\n');
+}
+
+/// Line index, level and title of all headings outside of code blocks.
+fn markdown_headings(lines: &[&str]) -> Vec<(usize, usize, String)> {
+    let mut in_code_block = false;
+
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+                in_code_block = !in_code_block;
+                return None;
+            }
+            if in_code_block {
+                return None;
+            }
+
+            MARKDOWN_HEADING_REGEX
+                .captures(line)
+                .map(|caps| (i, caps[1].len(), caps[2].to_owned()))
+        })
+        .collect()
+}
+
+/// An audio file in a playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub title: String,
+    /// Path of the audio file, relative to the playlist.
+    pub file: String,
+    pub duration: Duration,
+}
+
+/// Render tracks as an extended M3U playlist.
+pub fn to_m3u(tracks: &[Track]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for track in tracks {
+        m3u += &format!(
+            "#EXTINF:{},{}\n{}\n",
+            track.duration.as_secs_f64().round(),
+            track.title,
+            track.file
+        );
+    }
+    m3u
+}
+
+/// Render tracks as a CUE sheet with one file per track, which marks each track as a chapter.
+pub fn to_cue(title: &str, tracks: &[Track]) -> String {
+    let mut cue = format!("TITLE \"{}\"\n", escape_cue(title));
+    for (i, track) in tracks.iter().enumerate() {
+        cue += &format!(
+            "FILE \"{}\" WAVE\n  TRACK {:02} AUDIO\n    TITLE \"{}\"\n    INDEX 01 00:00:00\n",
+            escape_cue(&track.file),
+            i + 1,
+            escape_cue(&track.title)
+        );
+    }
+    cue
+}
+
+/// CUE sheets can't escape quotes, so they are replaced.
+fn escape_cue(text: &str) -> String {
+    text.replace('"', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_chapters_test() {
+        let book = "A Title Page\n\n\
+                    Chapter 1\n\n\
+                    It was a dark night.\n\
+                    Chapter 2 is mentioned here, but doesn't start a chapter.\n\n\
+                    CHAPTER II. The Storm\n\n\
+                    Rain.\n\n\
+                    Epilogue\n";
+
+        let expected = vec![
+            Chapter {
+                title: None,
+                text: "A Title Page".to_owned(),
+            },
+            Chapter {
+                title: Some("Chapter 1".to_owned()),
+                text: "It was a dark night.\nChapter 2 is mentioned here, but doesn't start a chapter."
+                    .to_owned(),
+            },
+            Chapter {
+                title: Some("CHAPTER II. The Storm".to_owned()),
+                text: "Rain.".to_owned(),
+            },
+            Chapter {
+                title: Some("Epilogue".to_owned()),
+                text: String::new(),
+            },
+        ];
+
+        assert_eq!(split_chapters(book, BookFormat::Plain), expected);
+    }
+
+    #[test]
+    fn markdown_chapters_test() {
+        let book = "## One\n\
+                    Text.\n\
+                    ### Section\n\
+                    More text.\n\
+                    ```\n\
+                    ## Not a heading\n\
+                    ```\n\
+                    ## Two ##\n\
+                    The end.";
+
+        let chapters = split_chapters(book, BookFormat::Markdown);
+
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title.as_deref(), Some("One"));
+        assert_eq!(
+            chapters[0].text,
+            "Text.\n\nSection\n\nMore text.\n```\n## Not a heading\n```"
+        );
+        assert_eq!(chapters[1].title.as_deref(), Some("Two"));
+        assert_eq!(chapters[1].text, "The end.");
+    }
+
+    #[test]
+    fn playlists_test() {
+        let tracks = vec![
+            Track {
+                title: "One".to_owned(),
+                file: "01 - One.wav".to_owned(),
+                duration: Duration::from_millis(61_600),
+            },
+            Track {
+                title: "The \"Two\"".to_owned(),
+                file: "02 - Two.wav".to_owned(),
+                duration: Duration::from_secs(5),
+            },
+        ];
+
+        assert_eq!(
+            to_m3u(&tracks),
+            "#EXTM3U\n#EXTINF:62,One\n01 - One.wav\n#EXTINF:5,The \"Two\"\n02 - Two.wav\n"
+        );
+        assert_eq!(
+            to_cue("Book", &tracks),
+            "TITLE \"Book\"\n\
+             FILE \"01 - One.wav\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"One\"\n    INDEX 01 00:00:00\n\
+             FILE \"02 - Two.wav\" WAVE\n  TRACK 02 AUDIO\n    TITLE \"The 'Two'\"\n    INDEX 01 00:00:00\n"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! Scripts with multiple speakers, rendered into one continuous track.
+//!
+//! A script assigns voices (or blends of voices) to speakers with `@speaker` directives, followed by the
+//! dialogue itself:
+//! ```text
+//! # Comments start with a hash
+//! @speaker ALICE af_bella pan=-0.4
+//! @speaker BOB am_adam*0.7+bm_daniel*0.3 pan=0.4 speed=1.1
+//! @gap 0.4
+//!
+//! ALICE: Welcome to the show!
+//! BOB: Thanks for having me.
+//!   Lines without a speaker continue the previous line.
+//! ```
+//! The voice of a speaker can be omitted, in which case a default voice is used. `pan` ranges from -1
+//! (left) to 1 (right), `@gap` sets the silence between lines in seconds.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail, ensure};
+
+use crate::{MAX_SPEED, MIN_SPEED, SAMPLE_RATE, VoiceBlend};
+
+/// Silence between lines in seconds, if not set by the script.
+static DEFAULT_GAP: f32 = 0.3;
+
+/// A speaker of a script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Speaker {
+    pub name: String,
+    pub voice: Option<VoiceBlend>,
+    /// Stereo position, from -1 (left) to 1 (right).
+    pub pan: f32,
+    /// Speaking rate, from [`MIN_SPEED`] to [`MAX_SPEED`].
+    pub speed: Option<f32>,
+}
+
+/// A line of a script, spoken by the speaker with index `speaker`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub speaker: usize,
+    pub text: String,
+}
+
+/// A parsed dialogue script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub speakers: Vec<Speaker>,
+    pub lines: Vec<Line>,
+    /// Silence between lines in seconds.
+    pub gap: f32,
+}
+
+impl FromStr for Script {
+    type Err = anyhow::Error;
+
+    fn from_str(script: &str) -> Result<Self> {
+        let mut speakers: Vec<Speaker> = Vec::new();
+        let mut lines: Vec<Line> = Vec::new();
+        let mut gap = DEFAULT_GAP;
+
+        for (i, line) in script.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(directive) = line.strip_prefix('@') {
+                let mut parts = directive.split_whitespace();
+                match parts.next() {
+                    Some("speaker") => {
+                        let speaker = parse_speaker(parts)
+                            .with_context(|| format!("Invalid speaker in line {line_num}"))?;
+                        ensure!(
+                            speakers.iter().all(|s| s.name != speaker.name),
+                            "Speaker '{}' in line {line_num} is already defined",
+                            speaker.name
+                        );
+                        speakers.push(speaker);
+                    }
+                    Some("gap") => {
+                        gap = parts
+                            .next()
+                            .unwrap_or_default()
+                            .parse()
+                            .with_context(|| format!("Invalid gap in line {line_num}"))?;
+                        ensure!(
+                            gap.is_finite() && gap >= 0.0,
+                            "Gap in line {line_num} must not be negative"
+                        );
+                    }
+                    _ => bail!("Unknown directive in line {line_num}: {line}"),
+                }
+                continue;
+            }
+
+            // A tag in capitals is always meant as a speaker, anything else might just be a colon in the text
+            let tag = line
+                .split_once(':')
+                .map(|(tag, text)| (tag.trim(), text.trim()))
+                .filter(|(tag, _)| !tag.is_empty() && !tag.contains(char::is_whitespace));
+            let speaker = tag.and_then(|(tag, text)| {
+                speakers
+                    .iter()
+                    .position(|s| s.name == tag)
+                    .map(|idx| (idx, text))
+            });
+
+            match (speaker, tag, lines.last_mut()) {
+                (Some((speaker, text)), _, _) => lines.push(Line {
+                    speaker,
+                    text: text.to_owned(),
+                }),
+                (None, Some((tag, _)), _)
+                    if tag.chars().any(char::is_alphabetic)
+                        && !tag.chars().any(char::is_lowercase) =>
+                {
+                    bail!("Unknown speaker '{tag}' in line {line_num}")
+                }
+                (None, _, Some(previous)) => {
+                    previous.text.push(' ');
+                    previous.text.push_str(line);
+                }
+                (None, _, None) => bail!("Line {line_num} has no speaker: {line}"),
+            }
+        }
+
+        Ok(Script {
+            speakers,
+            lines,
+            gap,
+        })
+    }
+}
+
+/// Parse `NAME [VOICE] [pan=PAN] [speed=SPEED]`.
+fn parse_speaker<'a>(mut parts: impl Iterator<Item = &'a str>) -> Result<Speaker> {
+    let Some(name) = parts.next() else {
+        bail!("Speaker has no name")
+    };
+
+    let mut speaker = Speaker {
+        name: name.to_owned(),
+        voice: None,
+        pan: 0.0,
+        speed: None,
+    };
+    for part in parts {
+        match part.split_once('=') {
+            Some(("pan", pan)) => {
+                speaker.pan = pan.parse().with_context(|| format!("Invalid pan '{pan}'"))?;
+                ensure!(
+                    (-1.0..=1.0).contains(&speaker.pan),
+                    "Pan must be between -1 and 1"
+                );
+            }
+            Some(("speed", speed)) => {
+                let speed: f32 = speed
+                    .parse()
+                    .with_context(|| format!("Invalid speed '{speed}'"))?;
+                ensure!(
+                    (MIN_SPEED..=MAX_SPEED).contains(&speed),
+                    "Speed must be between {MIN_SPEED} and {MAX_SPEED}, got {speed}"
+                );
+                speaker.speed = Some(speed);
+            }
+            Some((key, _)) => bail!("Unknown option '{key}'"),
+            None => {
+                ensure!(speaker.voice.is_none(), "Speaker has more than one voice");
+                speaker.voice = Some(part.parse()?);
+            }
+        }
+    }
+
+    Ok(speaker)
+}
+
+impl Script {
+    /// Render the script line by line, using `synthesize` to generate the audio of each line. Lines follow
+    /// each other, separated by the gap.
+    pub fn render(
+        &self,
+        mut synthesize: impl FnMut(&Line, &Speaker) -> Result<Vec<f32>>,
+    ) -> Result<Dialogue> {
+        let gap = (self.gap * SAMPLE_RATE as f32).round() as usize;
+
+        let mut lines = Vec::with_capacity(self.lines.len());
+        let mut position = 0;
+        for (i, line) in self.lines.iter().enumerate() {
+            let speaker = &self.speakers[line.speaker];
+            let audio = synthesize(line, speaker)?;
+
+            if i > 0 {
+                position += gap;
+            }
+            lines.push(RenderedLine {
+                speaker: speaker.name.clone(),
+                text: line.text.clone(),
+                pan: speaker.pan,
+                start: position,
+                audio,
+            });
+            position += lines.last().unwrap().audio.len();
+        }
+
+        Ok(Dialogue { lines })
+    }
+}
+
+/// A rendered script.
+#[derive(Debug, Clone)]
+pub struct Dialogue {
+    pub lines: Vec<RenderedLine>,
+}
+
+/// A rendered line, placed at sample `start` of the track.
+#[derive(Debug, Clone)]
+pub struct RenderedLine {
+    pub speaker: String,
+    pub text: String,
+    pub pan: f32,
+    pub start: usize,
+    pub audio: Vec<f32>,
+}
+
+impl RenderedLine {
+    /// First sample after the line.
+    pub fn end(&self) -> usize {
+        self.start + self.audio.len()
+    }
+}
+
+impl Dialogue {
+    /// Length of the track in samples.
+    pub fn len(&self) -> usize {
+        self.lines.iter().map(RenderedLine::end).max().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether any speaker is panned away from the center.
+    pub fn is_stereo(&self) -> bool {
+        self.lines.iter().any(|line| line.pan != 0.0)
+    }
+
+    /// Mix all lines into a mono track, ignoring panning.
+    pub fn to_mono(&self) -> Vec<f32> {
+        let mut track = vec![0f32; self.len()];
+        for line in &self.lines {
+            track[line.start..line.end()]
+                .iter_mut()
+                .zip(&line.audio)
+                .for_each(|(t, s)| *t += s);
+        }
+        track
+    }
+
+    /// Mix all lines into a stereo track with constant power panning.
+    pub fn to_stereo(&self) -> Vec<[f32; 2]> {
+        let mut track = vec![[0f32; 2]; self.len()];
+        for line in &self.lines {
+            let angle = (line.pan + 1.0) * std::f32::consts::FRAC_PI_4;
+            let (left, right) = (angle.cos(), angle.sin());
+
+            track[line.start..line.end()]
+                .iter_mut()
+                .zip(&line.audio)
+                .for_each(|(t, s)| {
+                    t[0] += s * left;
+                    t[1] += s * right;
+                });
+        }
+        track
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KokoroVoice;
+
+    #[test]
+    fn parse_script_test() {
+        let script = "# A podcast\n\
+                      @speaker ALICE af_bella pan=-0.5\n\
+                      @speaker BOB am_adam*0.7+bm_daniel*0.3 speed=1.1\n\
+                      @speaker NARRATOR\n\
+                      @gap 0.5\n\
+                      \n\
+                      NARRATOR: Welcome.\n\
+                      ALICE: Hi Bob!\n\
+                      BOB: Hello Alice.\n  \
+                      Note: this continues the line.\n";
+
+        let script: Script = script.parse().unwrap();
+
+        assert_eq!(script.gap, 0.5);
+        assert_eq!(script.speakers.len(), 3);
+        assert_eq!(script.speakers[0].voice, Some(KokoroVoice::AF_BELLA.into()));
+        assert_eq!(script.speakers[0].pan, -0.5);
+        assert_eq!(script.speakers[1].speed, Some(1.1));
+        assert_eq!(script.speakers[2].voice, None);
+        assert_eq!(
+            script.lines,
+            vec![
+                Line {
+                    speaker: 2,
+                    text: "Welcome.".to_owned()
+                },
+                Line {
+                    speaker: 0,
+                    text: "Hi Bob!".to_owned()
+                },
+                Line {
+                    speaker: 1,
+                    text: "Hello Alice. Note: this continues the line.".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_script_test() {
+        assert!("ALICE: Hi".parse::<Script>().is_err());
+        assert!("@speaker ALICE af_bella\nCAROL: Hi".parse::<Script>().is_err());
+        assert!("@speaker ALICE nobody".parse::<Script>().is_err());
+        assert!("@speaker ALICE pan=2".parse::<Script>().is_err());
+        assert!("@speaker ALICE speed=3".parse::<Script>().is_err());
+        assert!("@speaker ALICE speed=0.2".parse::<Script>().is_err());
+        assert!("@speaker ALICE\n@speaker ALICE".parse::<Script>().is_err());
+        assert!("@volume 3".parse::<Script>().is_err());
+    }
+
+    #[test]
+    fn render_test() {
+        let script: Script = "@speaker A pan=-1\n@speaker B pan=1\n@gap 0.5\nA: one\nB: two"
+            .parse()
+            .unwrap();
+        let dialogue = script.render(|_, _| Ok(vec![1.0; 100])).unwrap();
+
+        let gap = SAMPLE_RATE as usize / 2;
+        assert_eq!(dialogue.lines[1].start, 100 + gap);
+        assert_eq!(dialogue.len(), 200 + gap);
+
+        let stereo = dialogue.to_stereo();
+        assert!(stereo[0][0] > 0.99 && stereo[0][1].abs() < 1e-6);
+        assert!(stereo[100 + gap][0].abs() < 1e-6 && stereo[100 + gap][1] > 0.99);
+        assert_eq!(dialogue.to_mono()[100], 0.0);
+    }
+}
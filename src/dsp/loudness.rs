@@ -0,0 +1,342 @@
+//! Loudness measurement and normalization.
+//!
+//! Integrated loudness is measured as described in ITU-R BS.1770-4 / EBU R128, i.e. K-weighted, in
+//! overlapping blocks of 400ms, with an absolute gate at -70 LUFS and a relative gate at -10 LU. True peaks
+//! are estimated by oversampling the signal 4 times.
+
+use super::{db_to_gain, gain_to_db};
+
+/// Length of a gating block in seconds.
+static BLOCK_DURATION: f64 = 0.4;
+/// Blocks overlap by 75%, so a new block starts every quarter block.
+static BLOCK_STEPS: usize = 4;
+/// Blocks quieter than this (in LUFS) are ignored.
+static ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks quieter than the ungated loudness minus this (in LU) are ignored.
+static RELATIVE_GATE: f64 = 10.0;
+
+/// Oversampling factor used for true peak estimation.
+static TRUE_PEAK_OVERSAMPLING: usize = 4;
+/// Half width of the interpolation kernel used for true peak estimation, in samples.
+static TRUE_PEAK_HALF_WIDTH: isize = 6;
+
+/// Time the limiter needs to reach full gain reduction before a peak, in seconds.
+static LIMITER_LOOKAHEAD: f32 = 0.005;
+/// Time constant of the limiter gain recovering after a peak, in seconds.
+static LIMITER_RELEASE: f32 = 0.05;
+
+/// Way of normalizing audio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Scale the audio so that its sample peak is at `target` dBFS.
+    Peak { target: f32 },
+    /// Scale the audio so that its integrated loudness is at `target` LUFS, and limit true peaks to
+    /// `true_peak` dBTP.
+    Loudness { target: f32, true_peak: f32 },
+}
+
+impl Normalization {
+    /// Normalize the samples in place.
+    pub fn apply(&self, samples: &mut [f32], sample_rate: u32) {
+        match *self {
+            Normalization::Peak { target } => normalize_peak(samples, target),
+            Normalization::Loudness { target, true_peak } => {
+                normalize_loudness(samples, sample_rate, target, true_peak)
+            }
+        }
+    }
+}
+
+/// Scale the samples so that the highest sample peak is at `target` dBFS. Silence is left untouched.
+pub fn normalize_peak(samples: &mut [f32], target: f32) {
+    let peak = samples.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+    if peak == 0.0 {
+        return;
+    }
+
+    let gain = db_to_gain(target) / peak;
+    samples.iter_mut().for_each(|s| *s *= gain);
+}
+
+/// Scale the samples so that their integrated loudness is at `target` LUFS, then limit true peaks to
+/// `true_peak` dBTP. Audio that is too quiet to be measured is left untouched.
+pub fn normalize_loudness(samples: &mut [f32], sample_rate: u32, target: f32, true_peak: f32) {
+    let Some(loudness) = integrated_loudness(samples, sample_rate) else {
+        return;
+    };
+
+    let gain = db_to_gain(target - loudness);
+    samples.iter_mut().for_each(|s| *s *= gain);
+
+    limit_true_peak(samples, sample_rate, true_peak);
+}
+
+/// Highest sample peak in dBFS. Returns negative infinity for silence.
+pub fn sample_peak(samples: &[f32]) -> f32 {
+    gain_to_db(samples.iter().fold(0f32, |peak, s| peak.max(s.abs())))
+}
+
+/// Highest true (inter-sample) peak in dBTP. Returns negative infinity for silence.
+pub fn true_peak(samples: &[f32]) -> f32 {
+    gain_to_db(true_peak_envelope(samples).into_iter().fold(0f32, f32::max))
+}
+
+/// Integrated loudness in LUFS. Returns `None` if every block of the audio is below the gates, e.g. for
+/// silence or empty input.
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let weighted = k_weighting(sample_rate).iter().fold(
+        samples.iter().map(|&s| s as f64).collect::<Vec<f64>>(),
+        |signal, filter| filter.process(&signal),
+    );
+
+    // Audio shorter than one block is measured as a single block
+    let block_len = ((sample_rate as f64 * BLOCK_DURATION).round() as usize).max(1);
+    let step = (block_len / BLOCK_STEPS).max(1);
+    let powers: Vec<f64> = if weighted.len() <= block_len {
+        vec![mean_square(&weighted)]
+    } else {
+        (0..=(weighted.len() - block_len) / step)
+            .map(|i| mean_square(&weighted[i * step..i * step + block_len]))
+            .collect()
+    };
+
+    let above_absolute: Vec<f64> = powers
+        .into_iter()
+        .filter(|&p| block_loudness(p) > ABSOLUTE_GATE)
+        .collect();
+    if above_absolute.is_empty() {
+        return None;
+    }
+
+    let relative_gate = block_loudness(mean(&above_absolute)) - RELATIVE_GATE;
+    let above_relative: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|&p| block_loudness(p) > relative_gate)
+        .collect();
+    if above_relative.is_empty() {
+        return None;
+    }
+
+    Some(block_loudness(mean(&above_relative)) as f32)
+}
+
+/// Limit true peaks to `ceiling` dBTP with a look-ahead limiter, so that gain reduction sets in smoothly
+/// before a peak and recovers afterwards.
+pub fn limit_true_peak(samples: &mut [f32], sample_rate: u32, ceiling: f32) {
+    let ceiling = db_to_gain(ceiling);
+    let required: Vec<f32> = true_peak_envelope(samples)
+        .into_iter()
+        .map(|peak| if peak > ceiling { ceiling / peak } else { 1.0 })
+        .collect();
+
+    if required.iter().all(|&g| g == 1.0) {
+        return;
+    }
+
+    // Backwards: ramp down linearly within the look-ahead window before each peak
+    let ramp = 1.0 / (sample_rate as f32 * LIMITER_LOOKAHEAD).max(1.0);
+    let mut gain = required;
+    for n in (0..gain.len().saturating_sub(1)).rev() {
+        gain[n] = gain[n].min(gain[n + 1] + ramp);
+    }
+
+    // Forwards: recover exponentially after each peak
+    let release = (-1.0 / (sample_rate as f32 * LIMITER_RELEASE)).exp();
+    for n in 1..gain.len() {
+        gain[n] = gain[n].min(1.0 - (1.0 - gain[n - 1]) * release);
+    }
+
+    samples
+        .iter_mut()
+        .zip(gain)
+        .for_each(|(sample, gain)| *sample *= gain);
+}
+
+/// For every sample, the highest absolute value of the signal between that sample and the next one,
+/// estimated by windowed sinc interpolation.
+fn true_peak_envelope(samples: &[f32]) -> Vec<f32> {
+    let kernels: Vec<Vec<f32>> = (1..TRUE_PEAK_OVERSAMPLING)
+        .map(|phase| {
+            let offset = phase as f32 / TRUE_PEAK_OVERSAMPLING as f32;
+            (1 - TRUE_PEAK_HALF_WIDTH..=TRUE_PEAK_HALF_WIDTH)
+                .map(|tap| windowed_sinc(offset - tap as f32))
+                .collect()
+        })
+        .collect();
+
+    (0..samples.len())
+        .map(|n| {
+            kernels.iter().fold(samples[n].abs(), |peak, kernel| {
+                let value: f32 = (1 - TRUE_PEAK_HALF_WIDTH..=TRUE_PEAK_HALF_WIDTH)
+                    .zip(kernel)
+                    .filter_map(|(tap, k)| {
+                        let idx = n as isize + tap;
+                        (idx >= 0 && (idx as usize) < samples.len())
+                            .then(|| samples[idx as usize] * k)
+                    })
+                    .sum();
+                peak.max(value.abs())
+            })
+        })
+        .collect()
+}
+
+/// Sinc function with a Hann window spanning [`TRUE_PEAK_HALF_WIDTH`] samples on each side.
+fn windowed_sinc(x: f32) -> f32 {
+    let half_width = TRUE_PEAK_HALF_WIDTH as f32;
+    if x == 0.0 {
+        return 1.0;
+    }
+    if x.abs() >= half_width {
+        return 0.0;
+    }
+
+    let pi_x = std::f32::consts::PI * x;
+    let window = 0.5 * (1.0 + (pi_x / half_width).cos());
+    pi_x.sin() / pi_x * window
+}
+
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn mean_square(signal: &[f64]) -> f64 {
+    signal.iter().map(|s| s * s).sum::<f64>() / signal.len() as f64
+}
+
+/// Second order IIR filter.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&self, input: &[f64]) -> Vec<f64> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+
+        input
+            .iter()
+            .map(|&x| {
+                let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2
+                    - self.a[0] * y1
+                    - self.a[1] * y2;
+                (x2, x1, y2, y1) = (x1, x, y1, y);
+                y
+            })
+            .collect()
+    }
+}
+
+/// The two filter stages of the K-weighting (high shelf, then high pass), with coefficients derived for
+/// the given sample rate like in libebur128.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let shelf = {
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        }
+    };
+
+    let high_pass = {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        }
+    };
+
+    [shelf, high_pass]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SAMPLE_RATE;
+
+    fn sine(frequency: f32, amplitude: f32, seconds: f32) -> Vec<f32> {
+        let len = (SAMPLE_RATE as f32 * seconds) as usize;
+        (0..len)
+            .map(|n| {
+                amplitude
+                    * (2.0 * std::f32::consts::PI * frequency * n as f32 / SAMPLE_RATE as f32).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine_loudness_test() {
+        // BS.1770: a full scale 997Hz sine in one channel measures -3.01 LUFS
+        let audio = sine(997.0, 0.1, 3.0);
+        let loudness = integrated_loudness(&audio, SAMPLE_RATE).unwrap();
+
+        assert!((loudness - -23.01).abs() < 0.2, "measured {loudness} LUFS");
+    }
+
+    #[test]
+    fn silence_loudness_test() {
+        assert_eq!(integrated_loudness(&[0.0; 24000], SAMPLE_RATE), None);
+        assert_eq!(integrated_loudness(&[], SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn peak_normalization_test() {
+        let mut audio = sine(440.0, 0.3, 1.0);
+        Normalization::Peak { target: -1.0 }.apply(&mut audio, SAMPLE_RATE);
+
+        assert!((sample_peak(&audio) - -1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn loudness_normalization_test() {
+        let mut audio = sine(997.0, 0.05, 3.0);
+        Normalization::Loudness {
+            target: -16.0,
+            true_peak: -1.0,
+        }
+        .apply(&mut audio, SAMPLE_RATE);
+
+        let loudness = integrated_loudness(&audio, SAMPLE_RATE).unwrap();
+        assert!((loudness - -16.0).abs() < 0.2, "measured {loudness} LUFS");
+        assert!(true_peak(&audio) <= -1.0 + 0.1);
+    }
+
+    #[test]
+    fn limiter_test() {
+        let mut audio = sine(3000.0, 1.0, 1.0);
+        limit_true_peak(&mut audio, SAMPLE_RATE, -3.0);
+
+        assert!(
+            true_peak(&audio) <= -3.0 + 0.1,
+            "true peak {}",
+            true_peak(&audio)
+        );
+    }
+}
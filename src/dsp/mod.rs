@@ -0,0 +1,27 @@
+//! Post-processing for audio generated by Kokoro.
+pub mod loudness;
+pub mod stretch;
+
+/// Remove leading and trailing samples quieter than `threshold` dBFS.
+pub fn trim_silence(samples: &[f32], threshold: f32) -> &[f32] {
+    let threshold = db_to_gain(threshold);
+    let is_audible = |s: &f32| s.abs() > threshold;
+
+    match samples.iter().position(is_audible) {
+        Some(start) => {
+            let end = samples.iter().rposition(is_audible).unwrap();
+            &samples[start..=end]
+        }
+        None => &[],
+    }
+}
+
+/// Convert decibels to a linear gain factor.
+pub(crate) fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Convert a linear gain factor to decibels.
+pub(crate) fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
@@ -0,0 +1,173 @@
+//! Time-stretching and pitch-shifting.
+//!
+//! Time-stretching uses WSOLA (waveform similarity overlap-add): the audio is cut into overlapping, windowed
+//! frames, which are placed closer together or further apart. Each frame is shifted slightly within a
+//! tolerance so that it continues the previous one as smoothly as possible, which keeps the pitch intact.
+//! Pitch-shifting stretches the audio and resamples it back to the original duration.
+
+use anyhow::{Result, ensure};
+
+/// Length of a WSOLA frame in seconds.
+static WSOLA_FRAME_DURATION: f32 = 0.03;
+/// Maximum shift of a WSOLA frame from its nominal position in seconds.
+static WSOLA_TOLERANCE: f32 = 0.008;
+
+/// Change the tempo of the audio without changing its pitch. A tempo of `2.0` makes the audio twice as fast,
+/// i.e. half as long.
+pub fn time_stretch(samples: &[f32], sample_rate: u32, tempo: f32) -> Result<Vec<f32>> {
+    ensure!(
+        tempo.is_finite() && tempo > 0.0,
+        "Tempo must be a positive number, got {tempo}."
+    );
+    if tempo == 1.0 || samples.is_empty() {
+        return Ok(samples.to_vec());
+    }
+
+    let hop = ((sample_rate as f32 * WSOLA_FRAME_DURATION / 2.0) as usize).max(1);
+    let frame_len = 2 * hop;
+    let tolerance = (sample_rate as f32 * WSOLA_TOLERANCE) as isize;
+    // Periodic Hann window, overlapping windows sum up to 1
+    let window: Vec<f32> = (0..frame_len)
+        .map(|n| 0.5 - 0.5 * (std::f32::consts::TAU * n as f32 / frame_len as f32).cos())
+        .collect();
+
+    let input = |idx: isize| {
+        if idx >= 0 && (idx as usize) < samples.len() {
+            samples[idx as usize]
+        } else {
+            0.0
+        }
+    };
+
+    let out_len = (samples.len() as f32 / tempo).round() as usize;
+    let mut out = vec![0f32; out_len];
+
+    // The first frame starts one hop before the audio, so that the start isn't faded in
+    let mut out_pos = -(hop as isize);
+    let mut prev_pos: Option<isize> = None;
+    while out_pos < out_len as isize {
+        let nominal = (out_pos as f32 * tempo).round() as isize;
+
+        // Pick the frame most similar to the natural continuation of the previous frame
+        let pos = match prev_pos {
+            None => nominal,
+            Some(prev) => (-tolerance..=tolerance)
+                .map(|offset| {
+                    let similarity: f32 = (0..hop as isize)
+                        .map(|n| input(prev + hop as isize + n) * input(nominal + offset + n))
+                        .sum();
+                    (nominal + offset, similarity)
+                })
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(pos, _)| pos)
+                .unwrap_or(nominal),
+        };
+
+        for (n, w) in window.iter().enumerate() {
+            let idx = out_pos + n as isize;
+            if idx >= 0 && (idx as usize) < out_len {
+                out[idx as usize] += w * input(pos + n as isize);
+            }
+        }
+
+        prev_pos = Some(pos);
+        out_pos += hop as isize;
+    }
+
+    Ok(out)
+}
+
+/// Shift the pitch of the audio by the given number of semitones without changing its duration. Note that
+/// formants are shifted as well, so large shifts sound unnatural.
+pub fn pitch_shift(samples: &[f32], sample_rate: u32, semitones: f32) -> Result<Vec<f32>> {
+    ensure!(
+        semitones.is_finite(),
+        "Pitch shift must be a finite number, got {semitones}."
+    );
+    if semitones == 0.0 || samples.is_empty() {
+        return Ok(samples.to_vec());
+    }
+
+    let ratio = 2f32.powf(semitones / 12.0);
+    let stretched = time_stretch(samples, sample_rate, 1.0 / ratio)?;
+
+    let mut shifted = resample(&stretched, ratio);
+    shifted.resize(samples.len(), 0.0);
+
+    Ok(shifted)
+}
+
+/// Resample by linear interpolation, advancing `step` input samples per output sample.
+fn resample(samples: &[f32], step: f32) -> Vec<f32> {
+    let out_len = (samples.len() as f32 / step).round() as usize;
+
+    (0..out_len)
+        .map(|n| {
+            let pos = n as f32 * step;
+            let idx = pos as usize;
+            let frac = pos - idx as f32;
+
+            let current = samples.get(idx).copied().unwrap_or(0.0);
+            let next = samples.get(idx + 1).copied().unwrap_or(0.0);
+            current + (next - current) * frac
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SAMPLE_RATE;
+
+    fn sine(frequency: f32, seconds: f32) -> Vec<f32> {
+        let len = (SAMPLE_RATE as f32 * seconds) as usize;
+        (0..len)
+            .map(|n| (std::f32::consts::TAU * frequency * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    /// Estimate the frequency of a signal from its zero crossings, ignoring the edges.
+    fn frequency(samples: &[f32]) -> f32 {
+        let middle = &samples[samples.len() / 10..samples.len() * 9 / 10];
+        let crossings = middle
+            .windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count();
+
+        crossings as f32 / 2.0 / (middle.len() as f32 / SAMPLE_RATE as f32)
+    }
+
+    #[test]
+    fn time_stretch_test() {
+        let audio = sine(220.0, 1.0);
+
+        for tempo in [0.5, 0.8, 1.25, 2.0] {
+            let stretched = time_stretch(&audio, SAMPLE_RATE, tempo).unwrap();
+
+            assert_eq!(
+                stretched.len(),
+                (audio.len() as f32 / tempo).round() as usize
+            );
+            assert!((frequency(&stretched) - 220.0).abs() < 5.0, "tempo {tempo}");
+        }
+    }
+
+    #[test]
+    fn pitch_shift_test() {
+        let audio = sine(220.0, 1.0);
+
+        let up = pitch_shift(&audio, SAMPLE_RATE, 12.0).unwrap();
+        assert_eq!(up.len(), audio.len());
+        assert!((frequency(&up) - 440.0).abs() < 10.0);
+
+        let down = pitch_shift(&audio, SAMPLE_RATE, -12.0).unwrap();
+        assert_eq!(down.len(), audio.len());
+        assert!((frequency(&down) - 110.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn invalid_tempo_test() {
+        assert!(time_stretch(&[0.0; 100], SAMPLE_RATE, 0.0).is_err());
+        assert!(time_stretch(&[0.0; 100], SAMPLE_RATE, f32::NAN).is_err());
+    }
+}
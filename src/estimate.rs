@@ -0,0 +1,387 @@
+//! Estimating the duration of audio before generating it, e.g. for scheduling and progress bars.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{Result, ensure};
+
+use crate::{
+    Kokoro, KokoroVoice, VoiceBlend,
+    kokoro::{MAX_TOKENS, split_phonemes, tokenizer::KokoroTokenizer},
+    phonemes, utils,
+};
+
+/// Number of features the duration is estimated from, see [`features`].
+const FEATURES: usize = 5;
+
+/// Seconds of audio per chunk, phoneme, word boundary, clause pause and sentence pause at speed 1. These are
+/// rough guesses, multiples of Kokoro's frames of 25 ms, and not fitted against the model.
+static DEFAULT_WEIGHTS: [f64; FEATURES] = [0.25, 0.075, 0.015, 0.2, 0.3];
+
+/// Rounds of alternately fitting the weights and the factors of the voices.
+static CALIBRATION_ROUNDS: usize = 10;
+
+/// Estimates the duration of audio that [`Kokoro::phonemes2audio`] generates, without running the model.
+///
+/// Kokoro predicts a number of frames for every token and divides it by the speed. The duration is estimated
+/// as seconds per phoneme, word boundary and pause at punctuation, plus the silence around every chunk that is
+/// synthesized separately, divided by the speed and scaled by how fast the voice speaks. The default estimator
+/// is a rough guess, [`DurationEstimator::calibrate`] fits it to measurements of the model. Calibrated, the
+/// estimate for a sentence is expected to be within 20% of the actual duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationEstimator {
+    /// Seconds per feature at speed 1, in the order of [`features`].
+    weights: [f64; FEATURES],
+    /// How much slower than others voices speak.
+    voice_factors: HashMap<KokoroVoice, f64>,
+}
+
+/// Duration of audio that Kokoro generated, to calibrate a [`DurationEstimator`] with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub phonemes: String,
+    pub voice: KokoroVoice,
+    pub speed: f32,
+    pub duration: Duration,
+}
+
+impl Measurement {
+    /// Generate audio for phonemes and measure its duration.
+    pub fn measure(
+        kokoro: &Kokoro,
+        phonemes: &str,
+        voice: KokoroVoice,
+        speed: f32,
+    ) -> Result<Self> {
+        let audio = kokoro.phonemes2audio(phonemes, voice, speed)?;
+
+        Ok(Measurement {
+            phonemes: phonemes.to_owned(),
+            voice,
+            speed,
+            duration: utils::samples_to_duration(audio.len()),
+        })
+    }
+}
+
+impl Default for DurationEstimator {
+    fn default() -> Self {
+        DurationEstimator {
+            weights: DEFAULT_WEIGHTS,
+            voice_factors: HashMap::new(),
+        }
+    }
+}
+
+impl DurationEstimator {
+    /// Estimate the duration of audio for phonemes, in IPA or misaki, spoken by the voice at the speed.
+    pub fn estimate(&self, phonemes: &str, voice: &VoiceBlend, speed: f32) -> Result<Duration> {
+        ensure!(
+            speed.is_finite() && speed > 0.0,
+            "Speed must be a positive number, got {speed}."
+        );
+
+        let factor: f64 = voice
+            .voices()
+            .iter()
+            .map(|(voice, weight)| self.voice_factor(*voice) * *weight as f64)
+            .sum();
+        let seconds = self.seconds(&features(phonemes)) * factor / speed as f64;
+
+        Ok(Duration::from_secs_f64(seconds))
+    }
+
+    /// How much slower than others a voice speaks, e.g. 1.1 for a voice taking 10% longer. Voices that the
+    /// estimator wasn't calibrated with have a factor of 1.
+    pub fn voice_factor(&self, voice: KokoroVoice) -> f64 {
+        self.voice_factors.get(&voice).copied().unwrap_or(1.0)
+    }
+
+    /// Fit an estimator to durations measured with Kokoro, e.g. with [`Measurement::measure`]. The
+    /// measurements should cover sentences of different lengths and punctuation, and every voice that will be
+    /// estimated. Weights that the measurements say nothing about, e.g. of clause pauses if no phonemes have
+    /// a comma, keep their default.
+    pub fn calibrate(measurements: &[Measurement]) -> Result<Self> {
+        ensure!(
+            !measurements.is_empty(),
+            "Calibrating needs at least one measurement."
+        );
+        ensure!(
+            measurements
+                .iter()
+                .all(|m| m.speed.is_finite() && m.speed > 0.0),
+            "Speeds of measurements must be positive numbers."
+        );
+
+        // Features and seconds at speed 1 of every measurement
+        let samples: Vec<([f64; FEATURES], f64)> = measurements
+            .iter()
+            .map(|m| {
+                let seconds = m.duration.as_secs_f64() * m.speed as f64;
+                (features(&m.phonemes), seconds)
+            })
+            .collect();
+
+        let mut estimator = DurationEstimator::default();
+        for _ in 0..CALIBRATION_ROUNDS {
+            let voiceless: Vec<([f64; FEATURES], f64)> = measurements
+                .iter()
+                .zip(&samples)
+                .map(|(m, (features, seconds))| {
+                    (*features, seconds / estimator.voice_factor(m.voice))
+                })
+                .collect();
+            estimator.weights = least_squares(&voiceless);
+
+            // Factor minimizing the squared error of the estimates of each voice
+            let mut sums: HashMap<KokoroVoice, (f64, f64)> = HashMap::new();
+            for (m, (features, seconds)) in measurements.iter().zip(&samples) {
+                let estimate = estimator.seconds(features);
+                let (product, square) = sums.entry(m.voice).or_default();
+                *product += estimate * seconds;
+                *square += estimate * estimate;
+            }
+            estimator.voice_factors = sums
+                .into_iter()
+                .filter(|(_, (_, square))| *square > 0.0)
+                .map(|(voice, (product, square))| (voice, product / square))
+                .collect();
+        }
+
+        Ok(estimator)
+    }
+
+    /// Relative time spent on each token of a chunk, with the padding before and after the tokens, which holds
+    /// the silence around the chunk.
+    pub(crate) fn token_weights(&self, tokens: &[char]) -> Vec<f32> {
+        let padding = self.weights[0] as f32 / 2.0;
+
+        let mut weights = vec![padding];
+        weights.extend(
+            tokens
+                .iter()
+                .map(|c| feature(*c).map_or(0.0, |feature| self.weights[feature] as f32)),
+        );
+        weights.push(padding);
+        weights
+    }
+
+    /// Estimated seconds at speed 1 for an average voice.
+    fn seconds(&self, features: &[f64; FEATURES]) -> f64 {
+        features.iter().zip(&self.weights).map(|(f, w)| f * w).sum()
+    }
+}
+
+/// What audio is spent on, as counts of: chunks Kokoro synthesizes separately, phonemes, word boundaries, and
+/// pauses at clauses and sentences. Stress marks and other punctuation don't add to the duration.
+fn features(phonemes: &str) -> [f64; FEATURES] {
+    let phonemes = phonemes::to_kokoro(phonemes);
+    let mut features = [0.0; FEATURES];
+
+    for chunk in split_phonemes(&phonemes, MAX_TOKENS) {
+        if chunk.is_empty() {
+            continue;
+        }
+
+        features[0] += 1.0;
+        for c in chunk.chars().filter(|c| KokoroTokenizer::is_known(*c)) {
+            if let Some(feature) = feature(c) {
+                features[feature] += 1.0;
+            }
+        }
+    }
+
+    features
+}
+
+/// Feature a token counts towards, if any.
+fn feature(token: char) -> Option<usize> {
+    match token {
+        'ˈ' | 'ˌ' => None,
+        c if c.is_alphabetic() => Some(1),
+        ' ' => Some(2),
+        ',' | ';' | ':' | '—' => Some(3),
+        '.' | '!' | '?' | '…' => Some(4),
+        _ => None,
+    }
+}
+
+/// Weights minimizing the squared error of the estimated seconds. The fit is regularized slightly towards the
+/// default weights, so that features missing from the samples keep them.
+fn least_squares(samples: &[([f64; FEATURES], f64)]) -> [f64; FEATURES] {
+    // Normal equations
+    let mut matrix = [[0.0; FEATURES]; FEATURES];
+    let mut vector = [0.0; FEATURES];
+    for (features, seconds) in samples {
+        for (row, a) in matrix.iter_mut().zip(features) {
+            for (cell, b) in row.iter_mut().zip(features) {
+                *cell += a * b;
+            }
+        }
+        for (value, a) in vector.iter_mut().zip(features) {
+            *value += a * seconds;
+        }
+    }
+    for (i, default) in DEFAULT_WEIGHTS.iter().enumerate() {
+        let regularization = 1e-6 * matrix[i][i].max(1.0);
+        matrix[i][i] += regularization;
+        vector[i] += regularization * default;
+    }
+
+    solve(matrix, vector).map(|weight| weight.max(0.0))
+}
+
+/// Solve a system of linear equations by Gaussian elimination. The matrix must be positive definite, which the
+/// regularized normal equations are.
+fn solve(mut matrix: [[f64; FEATURES]; FEATURES], mut vector: [f64; FEATURES]) -> [f64; FEATURES] {
+    for column in 0..FEATURES {
+        for row in column + 1..FEATURES {
+            let factor = matrix[row][column] / matrix[column][column];
+            let pivot = matrix[column];
+            for (cell, value) in matrix[row].iter_mut().zip(pivot) {
+                *cell -= factor * value;
+            }
+            vector[row] -= factor * vector[column];
+        }
+    }
+
+    let mut solution = [0.0; FEATURES];
+    for row in (0..FEATURES).rev() {
+        let known: f64 = (row + 1..FEATURES)
+            .map(|column| matrix[row][column] * solution[column])
+            .sum();
+        solution[row] = (vector[row] - known) / matrix[row][row];
+    }
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SENTENCES: [&str; 8] = [
+        "həlˈO wˈɜɹld.",
+        "ðə kwˈɪk bɹˈWn fˈɑks ʤˈʌmpt ˌOvəɹ ðə lˈAzi dˈɔɡ.",
+        "jˈɛs, ˈI θˈɪŋk sˌO.",
+        "wˈʌn, tˈu, θɹˈi, fˈɔɹ!",
+        "nˈO.",
+        "hˈW ˈɑɹ ju tədˈA? ˈIm fˈIn, θˈæŋk ju.",
+        "kˈOkəɹO ɪz ə tˈɛkst tə spˈiʧ mˈɑdᵊl",
+        "wˈAt… ɹˈiᵊli?",
+    ];
+
+    #[test]
+    fn features_test() {
+        assert_eq!(features("həlˈoʊ wˈɜɹld, jˈɛs."), [1.0, 12.0, 2.0, 1.0, 1.0]);
+        assert_eq!(features(""), [0.0; FEATURES]);
+
+        // Phonemes over the token limit are synthesized in chunks
+        let long = vec!["ðə kˈæt."; 100].join(" ");
+        assert_eq!(features(&long)[0], 2.0);
+    }
+
+    #[test]
+    fn estimate_test() -> Result<()> {
+        let estimator = DurationEstimator::default();
+        let voice = VoiceBlend::from(KokoroVoice::AF_BELLA);
+
+        let short = estimator.estimate(SENTENCES[0], &voice, 1.0)?;
+        let long = estimator.estimate(SENTENCES[1], &voice, 1.0)?;
+        assert!(short > Duration::from_millis(500) && short < long);
+
+        let fast = estimator.estimate(SENTENCES[1], &voice, 2.0)?;
+        assert!((fast.as_secs_f64() * 2.0 - long.as_secs_f64()).abs() < 1e-6);
+
+        assert_eq!(estimator.estimate("", &voice, 1.0)?, Duration::ZERO);
+        assert!(estimator.estimate(SENTENCES[0], &voice, 0.0).is_err());
+
+        Ok(())
+    }
+
+    /// Calibrating with durations that follow the model of the estimator recovers it to within 1%.
+    #[test]
+    fn calibrate_test() -> Result<()> {
+        let truth = DurationEstimator {
+            weights: [0.3, 0.08, 0.02, 0.25, 0.4],
+            voice_factors: HashMap::from([
+                (KokoroVoice::AF_BELLA, 1.1),
+                (KokoroVoice::BM_DANIEL, 0.9),
+            ]),
+        };
+        let voices = [KokoroVoice::AF_BELLA, KokoroVoice::BM_DANIEL];
+
+        let mut measurements = Vec::new();
+        for phonemes in &SENTENCES[..6] {
+            for (voice, speed) in voices.into_iter().zip([0.8, 1.5]) {
+                measurements.push(Measurement {
+                    phonemes: phonemes.to_string(),
+                    voice,
+                    speed,
+                    duration: truth.estimate(phonemes, &voice.into(), speed)?,
+                });
+            }
+        }
+        let estimator = DurationEstimator::calibrate(&measurements)?;
+
+        for phonemes in &SENTENCES[6..] {
+            for voice in voices {
+                let voice = VoiceBlend::from(voice);
+                let expected = truth.estimate(phonemes, &voice, 1.2)?.as_secs_f64();
+                let estimated = estimator.estimate(phonemes, &voice, 1.2)?.as_secs_f64();
+                assert!(
+                    (estimated - expected).abs() < 0.01 * expected,
+                    "{phonemes:?}: {estimated} instead of {expected}"
+                );
+            }
+        }
+
+        assert!(DurationEstimator::calibrate(&[]).is_err());
+
+        Ok(())
+    }
+
+    /// Estimates calibrated with half of the sentences are within 20% of the duration Kokoro generates for the
+    /// other half.
+    #[test]
+    #[cfg_attr(
+        not(feature = "download-data"),
+        ignore = "runs the Kokoro model, which needs the feature download-data"
+    )]
+    fn estimate_matches_kokoro_test() -> Result<()> {
+        let kokoro = Kokoro::new()?;
+        let voices = [
+            KokoroVoice::AF_HEART,
+            KokoroVoice::AM_ADAM,
+            KokoroVoice::BF_EMMA,
+        ];
+
+        let mut calibration = Vec::new();
+        let mut evaluation = Vec::new();
+        for (i, phonemes) in SENTENCES.iter().enumerate() {
+            for voice in voices {
+                for speed in [0.8, 1.0, 1.4] {
+                    let measurement = Measurement::measure(&kokoro, phonemes, voice, speed)?;
+                    match i % 2 {
+                        0 => calibration.push(measurement),
+                        _ => evaluation.push(measurement),
+                    }
+                }
+            }
+        }
+        let estimator = DurationEstimator::calibrate(&calibration)?;
+
+        for m in evaluation {
+            let estimated = estimator.estimate(&m.phonemes, &m.voice.into(), m.speed)?;
+            let error = (estimated.as_secs_f64() / m.duration.as_secs_f64() - 1.0).abs();
+            assert!(
+                error <= 0.2,
+                "{:?} by {:?} at speed {}: estimated {estimated:?}, generated {:?}",
+                m.phonemes,
+                m.voice,
+                m.speed,
+                m.duration
+            );
+        }
+
+        Ok(())
+    }
+}
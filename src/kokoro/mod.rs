@@ -1,12 +1,19 @@
-use anyhow::{Context, Result};
+use std::{borrow::Cow, str::FromStr};
+
+use anyhow::{Context, Result, ensure};
 use ndarray::{Array1, Array2};
 use ort::execution_providers;
 use ort::session::Session;
 use speakoro_macros::associate_static_data;
-use strum::{EnumString, VariantNames};
+use strum::{Display, EnumString, VariantNames};
 use tokenizer::KokoroTokenizer;
 
-mod tokenizer;
+use crate::{
+    estimate::DurationEstimator, phonemes, phonemizer::Phonemizer, text::overrides,
+    timings::TimedAudio,
+};
+
+pub(crate) mod tokenizer;
 
 /// This macro includes a binary file and transmutes it to the requested type with correct alignment. Note that
 /// safety still needs to be guaranteed by the user.
@@ -19,7 +26,7 @@ macro_rules! include_transmute {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, EnumString, VariantNames)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, VariantNames)]
 #[strum(serialize_all = "lowercase")]
 /// All currently supported voices for Kokoro, which is a selection of voices I liked.
 pub enum KokoroVoice {
@@ -36,6 +43,26 @@ pub enum KokoroVoice {
     AM_FENRIR,
     // Male - British
     BM_DANIEL,
+    // Spanish
+    EF_DORA,
+    EM_ALEX,
+    // French
+    FF_SIWIS,
+    // Hindi
+    HF_ALPHA,
+    HM_OMEGA,
+    // Italian
+    IF_SARA,
+    IM_NICOLA,
+    // Japanese
+    JF_ALPHA,
+    JM_KUMO,
+    // Brazilian Portuguese
+    PF_DORA,
+    PM_ALEX,
+    // Mandarin Chinese
+    ZF_XIAOBEI,
+    ZM_YUNJIAN,
 }
 
 associate_static_data!(
@@ -52,9 +79,35 @@ associate_static_data!(
     KokoroVoice::AM_ADAM        => unsafe { include_transmute!("../../data/voices/am_adam.bin") },
     KokoroVoice::AM_FENRIR      => unsafe { include_transmute!("../../data/voices/am_fenrir.bin") },
     KokoroVoice::BM_DANIEL      => unsafe { include_transmute!("../../data/voices/bm_daniel.bin") },
+    KokoroVoice::EF_DORA        => unsafe { include_transmute!("../../data/voices/ef_dora.bin") },
+    KokoroVoice::EM_ALEX        => unsafe { include_transmute!("../../data/voices/em_alex.bin") },
+    KokoroVoice::FF_SIWIS       => unsafe { include_transmute!("../../data/voices/ff_siwis.bin") },
+    KokoroVoice::HF_ALPHA       => unsafe { include_transmute!("../../data/voices/hf_alpha.bin") },
+    KokoroVoice::HM_OMEGA       => unsafe { include_transmute!("../../data/voices/hm_omega.bin") },
+    KokoroVoice::IF_SARA        => unsafe { include_transmute!("../../data/voices/if_sara.bin") },
+    KokoroVoice::IM_NICOLA      => unsafe { include_transmute!("../../data/voices/im_nicola.bin") },
+    KokoroVoice::JF_ALPHA       => unsafe { include_transmute!("../../data/voices/jf_alpha.bin") },
+    KokoroVoice::JM_KUMO        => unsafe { include_transmute!("../../data/voices/jm_kumo.bin") },
+    KokoroVoice::PF_DORA        => unsafe { include_transmute!("../../data/voices/pf_dora.bin") },
+    KokoroVoice::PM_ALEX        => unsafe { include_transmute!("../../data/voices/pm_alex.bin") },
+    KokoroVoice::ZF_XIAOBEI     => unsafe { include_transmute!("../../data/voices/zf_xiaobei.bin") },
+    KokoroVoice::ZM_YUNJIAN     => unsafe { include_transmute!("../../data/voices/zm_yunjian.bin") },
 );
 
+/// Sample rate of the audio generated by Kokoro.
+pub static SAMPLE_RATE: u32 = 24000;
+
+/// Bounds for the speaking rate of the model.
+pub static MIN_SPEED: f32 = 0.5;
+pub static MAX_SPEED: f32 = 2.0;
+
 static KOKORO_STYLE_DIM: usize = 256;
+/// Number of style vectors per voice. Since the style is chosen by the number of tokens (including padding),
+/// this limits the length of the input.
+static KOKORO_STYLE_NUM: usize = 510;
+/// Tokens of phonemes Kokoro reads in one inference, as 2 tokens are needed for padding.
+pub(crate) static MAX_TOKENS: usize = KOKORO_STYLE_NUM - 3;
+
 impl KokoroVoice {
     // Better output type: &[f32; KOKORO_STYLE_DIM]
     /// Return style data used by Kokoro for a given token num.
@@ -64,14 +117,209 @@ impl KokoroVoice {
 
         &voice_data[offset..offset + KOKORO_STYLE_DIM]
     }
+
+    /// Language and accent the voice speaks with.
+    pub fn language(&self) -> Language {
+        match self {
+            KokoroVoice::AF_HEART
+            | KokoroVoice::AF_BELLA
+            | KokoroVoice::AF_NICOLE
+            | KokoroVoice::AF_AOEDE
+            | KokoroVoice::AM_ADAM
+            | KokoroVoice::AM_FENRIR => Language::EnUs,
+            KokoroVoice::BF_EMMA | KokoroVoice::BF_ISABELLA | KokoroVoice::BM_DANIEL => {
+                Language::EnGb
+            }
+            KokoroVoice::EF_DORA | KokoroVoice::EM_ALEX => Language::Es,
+            KokoroVoice::FF_SIWIS => Language::Fr,
+            KokoroVoice::HF_ALPHA | KokoroVoice::HM_OMEGA => Language::Hi,
+            KokoroVoice::IF_SARA | KokoroVoice::IM_NICOLA => Language::It,
+            KokoroVoice::JF_ALPHA | KokoroVoice::JM_KUMO => Language::Ja,
+            KokoroVoice::PF_DORA | KokoroVoice::PM_ALEX => Language::PtBr,
+            KokoroVoice::ZF_XIAOBEI | KokoroVoice::ZM_YUNJIAN => Language::Zh,
+        }
+    }
+}
+
+/// Languages, including their accent, that voices speak with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Language {
+    /// American English
+    EnUs,
+    /// British English
+    EnGb,
+    /// Spanish
+    Es,
+    /// French
+    Fr,
+    /// Hindi
+    Hi,
+    /// Italian
+    It,
+    /// Japanese
+    Ja,
+    /// Brazilian Portuguese
+    PtBr,
+    /// Mandarin Chinese
+    Zh,
+}
+
+impl Language {
+    /// Whether the language is English, which text normalization and the built-in phonemizer support.
+    pub fn is_english(&self) -> bool {
+        matches!(self, Language::EnUs | Language::EnGb)
+    }
+}
+
+/// Anything that provides the style data Kokoro is conditioned on.
+pub trait Voice {
+    /// Return style data used by Kokoro for a given token num.
+    fn style(&self, token_num: usize) -> Cow<'_, [f32]>;
+
+    /// Language and accent of the voice, if known.
+    fn language(&self) -> Option<Language> {
+        None
+    }
+}
+
+impl Voice for KokoroVoice {
+    fn style(&self, token_num: usize) -> Cow<'_, [f32]> {
+        Cow::Borrowed(KokoroVoice::style(self, token_num))
+    }
+
+    fn language(&self) -> Option<Language> {
+        Some(KokoroVoice::language(self))
+    }
+}
+
+impl<V: Voice + ?Sized> Voice for &V {
+    fn style(&self, token_num: usize) -> Cow<'_, [f32]> {
+        (**self).style(token_num)
+    }
+
+    fn language(&self) -> Option<Language> {
+        (**self).language()
+    }
+}
+
+/// Weighted mix of voices. Can be parsed from strings like `af_bella+af_nicole` (equal weights) or
+/// `af_bella*0.7+bf_emma*0.3`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceBlend {
+    voices: Vec<(KokoroVoice, f32)>,
+}
+
+impl VoiceBlend {
+    /// Create a blend of voices with the given weights, which are normalized to sum up to 1.
+    pub fn new(voices: Vec<(KokoroVoice, f32)>) -> Result<Self> {
+        ensure!(!voices.is_empty(), "A voice blend needs at least one voice.");
+        ensure!(
+            voices.iter().all(|(_, w)| w.is_finite() && *w > 0.0),
+            "Weights of a voice blend must be positive."
+        );
+
+        let total: f32 = voices.iter().map(|(_, w)| w).sum();
+        Ok(VoiceBlend {
+            voices: voices.into_iter().map(|(v, w)| (v, w / total)).collect(),
+        })
+    }
+
+    /// The blended voices and their normalized weights.
+    pub fn voices(&self) -> &[(KokoroVoice, f32)] {
+        &self.voices
+    }
+
+    /// Language of the voice with the highest weight.
+    pub fn language(&self) -> Language {
+        self.voices
+            .iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(voice, _)| voice.language())
+            .unwrap()
+    }
+}
+
+impl From<KokoroVoice> for VoiceBlend {
+    fn from(voice: KokoroVoice) -> Self {
+        VoiceBlend {
+            voices: vec![(voice, 1.0)],
+        }
+    }
+}
+
+impl FromStr for VoiceBlend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let voices = s
+            .split('+')
+            .map(|part| {
+                let (name, weight) = match part.split_once('*') {
+                    Some((name, weight)) => (
+                        name.trim(),
+                        weight
+                            .trim()
+                            .parse::<f32>()
+                            .with_context(|| format!("Invalid weight for voice '{}'", name.trim()))?,
+                    ),
+                    None => (part.trim(), 1.0),
+                };
+                let voice = KokoroVoice::from_str(name)
+                    .with_context(|| format!("Unknown voice '{name}'"))?;
+
+                Ok((voice, weight))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        VoiceBlend::new(voices)
+    }
+}
+
+impl Voice for VoiceBlend {
+    fn style(&self, token_num: usize) -> Cow<'_, [f32]> {
+        let mut style = vec![0f32; KOKORO_STYLE_DIM];
+        for (voice, weight) in &self.voices {
+            style
+                .iter_mut()
+                .zip(KokoroVoice::style(voice, token_num))
+                .for_each(|(s, v)| *s += weight * v);
+        }
+
+        Cow::Owned(style)
+    }
+
+    fn language(&self) -> Option<Language> {
+        Some(VoiceBlend::language(self))
+    }
 }
 
 static KOKORO_MODEL_BIN: &[u8] =
     include_bytes!(concat!("../../data/onnx/", env!("SPEAKORO_MODEL_FILE")));
 
+/// Names of outputs with the number of frames predicted for every token, which some exports of Kokoro have
+/// besides the waveform.
+static DURATION_OUTPUTS: [&str; 3] = ["durations", "duration", "pred_dur"];
+
+/// Options for turning text into phonemes in [`Kokoro::text2audio_with_options`].
+#[derive(Debug, Clone, Copy)]
+pub struct TextOptions {
+    /// Expand numbers, dates and abbreviations into words in the language of the voice before phonemizing, see
+    /// [`normalize`](crate::text::normalize::normalize). On by default.
+    pub normalize: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions { normalize: true }
+    }
+}
+
 /// Struct representing the Kokoro model.
 pub struct Kokoro {
     model: Session,
+    /// Name of the output with predicted durations, if the model has one.
+    durations: Option<String>,
 }
 
 impl Kokoro {
@@ -85,21 +333,157 @@ impl Kokoro {
             execution_providers::CPUExecutionProvider::default().build(),
         ];
 
-        Ok(Kokoro {
-            model: Session::builder()?
-                .with_execution_providers(ep)?
-                .commit_from_memory(KOKORO_MODEL_BIN)
-                .with_context(|| "Loading onnx model failed")?, // TODO: Add options like optimization level
-        })
+        let model = Session::builder()?
+            .with_execution_providers(ep)?
+            .commit_from_memory(KOKORO_MODEL_BIN)
+            .with_context(|| "Loading onnx model failed")?; // TODO: Add options like optimization level
+        let durations = model
+            .outputs
+            .iter()
+            .map(|output| output.name.clone())
+            .find(|name| DURATION_OUTPUTS.contains(&name.as_str()));
+
+        Ok(Kokoro { model, durations })
+    }
+
+    /// Whether the model outputs the durations it predicted for the tokens, which makes the timings of
+    /// [`Kokoro::phonemes2audio_timed`] exact.
+    pub fn predicts_durations(&self) -> bool {
+        self.durations.is_some()
     }
 
     /// Generate audio from phonemes. Output are WAV samples.
-    pub fn phonemes2audio(
+    ///
+    /// Phonemes are mapped onto the symbols Kokoro reads with [`phonemes::to_kokoro`], so both IPA and misaki
+    /// phonemes can be passed. Phonemes exceeding the token limit of Kokoro are split at sentence, clause or word
+    /// boundaries, and the chunks are synthesized separately.
+    pub fn phonemes2audio<V: Voice>(
         &self,
         phonemes: &str,
-        voice: KokoroVoice,
+        voice: V,
+        speed: f32,
+    ) -> Result<Vec<f32>> {
+        let phonemes = phonemes::to_kokoro(phonemes);
+        let mut audio = Vec::new();
+        for chunk in split_phonemes(&phonemes, MAX_TOKENS) {
+            audio.extend(self.infer(chunk, &voice, speed)?.0);
+        }
+
+        Ok(audio)
+    }
+
+    /// Like [`Kokoro::phonemes2audio`], but also returns the range of samples every token was spoken in.
+    ///
+    /// The timings are exact if the model outputs the durations it predicted, see
+    /// [`Kokoro::predicts_durations`]. Otherwise, the samples of each chunk are split among its tokens as
+    /// [`DurationEstimator`] expects them to be spoken, and the result is marked as estimated.
+    pub fn phonemes2audio_timed<V: Voice>(
+        &self,
+        phonemes: &str,
+        voice: V,
+        speed: f32,
+    ) -> Result<TimedAudio> {
+        let estimator = DurationEstimator::default();
+        let phonemes = phonemes::to_kokoro(phonemes);
+
+        let mut timed = TimedAudio::default();
+        for chunk in split_phonemes(&phonemes, MAX_TOKENS) {
+            let (audio, durations) = self.infer(chunk, &voice, speed)?;
+
+            let tokens: Vec<char> = chunk
+                .chars()
+                .filter(|c| KokoroTokenizer::is_known(*c))
+                .collect();
+            // Durations include the padding
+            let weights = match durations {
+                Some(durations) if durations.len() == tokens.len() + 2 => durations,
+                _ => {
+                    timed.estimated = true;
+                    estimator.token_weights(&tokens)
+                }
+            };
+            timed.push_chunk(audio, &tokens, &weights);
+        }
+
+        Ok(timed)
+    }
+
+    /// Generate audio from text, using [Phonemoro](https://github.com/lastleon/phonemoro) as the phonemizer.
+    /// Output are WAV samples.
+    ///
+    /// British voices are given British phonemes with
+    /// [`BritishPhonemizer`](crate::phonemizer::BritishPhonemizer), voices of other languages than English fail.
+    /// The phonemizer is loaded on first use, once per thread. Use [`Kokoro::text2audio_with`] to pass a different
+    /// phonemizer.
+    #[cfg(feature = "phonemoro")]
+    pub fn text2audio<V: Voice>(&self, text: &str, voice: V, speed: f32) -> Result<Vec<f32>> {
+        use crate::phonemizer::BritishPhonemizer;
+        use phonemoro::en::phonemizer::EnPhonemizer;
+
+        thread_local! {
+            static PHONEMIZER: std::cell::OnceCell<EnPhonemizer> = const { std::cell::OnceCell::new() };
+        }
+
+        PHONEMIZER.with(|cell| {
+            if cell.get().is_none() {
+                let _ = cell.set(EnPhonemizer::new().with_context(|| "Loading phonemizer failed")?);
+            }
+            let phonemizer = cell.get().unwrap();
+
+            match voice.language() {
+                Some(Language::EnGb) => self.text2audio_with(
+                    &BritishPhonemizer::new(phonemizer),
+                    text,
+                    voice,
+                    speed,
+                ),
+                Some(language) if !language.is_english() => anyhow::bail!(
+                    "Phonemoro only supports English, use Kokoro::text2audio_with with a phonemizer for '{language}'"
+                ),
+                _ => self.text2audio_with(phonemizer, text, voice, speed),
+            }
+        })
+    }
+
+    /// Generate audio from text, using the given phonemizer and the default [`TextOptions`]. Output are WAV
+    /// samples.
+    ///
+    /// Numbers, dates and abbreviations are expanded into words in the language of the voice, see
+    /// [`normalize`](crate::text::normalize::normalize). Words can be given their own pronunciation inline, like
+    /// `[Kokoro](/kˈOkəɹO/)`, be spelled out or emphasized, see [`overrides`].
+    pub fn text2audio_with<P: Phonemizer + ?Sized, V: Voice>(
+        &self,
+        phonemizer: &P,
+        text: &str,
+        voice: V,
+        speed: f32,
+    ) -> Result<Vec<f32>> {
+        self.text2audio_with_options(phonemizer, text, voice, speed, TextOptions::default())
+    }
+
+    /// Generate audio from text, using the given phonemizer and options, e.g. to pass numbers to the phonemizer as
+    /// they are. Output are WAV samples.
+    pub fn text2audio_with_options<P: Phonemizer + ?Sized, V: Voice>(
+        &self,
+        phonemizer: &P,
+        text: &str,
+        voice: V,
         speed: f32,
+        options: TextOptions,
     ) -> Result<Vec<f32>> {
+        let language = voice.language().unwrap_or(Language::EnUs);
+        let phonemes = overrides::phonemize(text, phonemizer, language, options.normalize)?;
+        self.phonemes2audio(&phonemes, voice, speed)
+    }
+
+    /// Generate audio for phonemes within the token limit. Also returns the number of frames predicted for every
+    /// token, including the padding, if the model outputs them.
+    fn infer(
+        &self,
+        phonemes: &str,
+        voice: &impl Voice,
+        speed: f32,
+    ) -> Result<(Vec<f32>, Option<Vec<f32>>)> {
         // tokenize and prepare input
         let input_ids = {
             let tokens: Vec<i64> = KokoroTokenizer::tokenize(phonemes)
@@ -111,8 +495,10 @@ impl Kokoro {
         };
 
         // Prepare voice style
-        let style =
-            Array2::from_shape_vec((1, KOKORO_STYLE_DIM), voice.style(input_ids.len()).to_vec())?;
+        let style = Array2::from_shape_vec(
+            (1, KOKORO_STYLE_DIM),
+            voice.style(input_ids.len()).into_owned(),
+        )?;
 
         // Speed
         let speed = Array1::from_vec(vec![speed]);
@@ -126,6 +512,107 @@ impl Kokoro {
 
         let wav_samples = outputs["waveform"].try_extract_tensor::<f32>()?;
 
-        Ok(wav_samples.iter().map(|v| *v).collect())
+        // Frames are rounded to integers, but may be given as floats
+        let durations = match self.durations.as_ref().and_then(|name| outputs.get(name)) {
+            Some(durations) => Some(match durations.try_extract_tensor::<i64>() {
+                Ok(frames) => frames.iter().map(|f| *f as f32).collect(),
+                Err(_) => durations.try_extract_tensor::<f32>()?.iter().copied().collect(),
+            }),
+            None => None,
+        };
+
+        Ok((wav_samples.iter().map(|v| *v).collect(), durations))
+    }
+}
+
+/// Split phonemes into chunks of at most `max_tokens` tokens. Each split is placed after the last sentence
+/// boundary within the limit, or else the last clause boundary, or else the last word boundary.
+pub(crate) fn split_phonemes(phonemes: &str, max_tokens: usize) -> Vec<&str> {
+    let boundaries: [&[char]; 3] = [&['.', '!', '?', '…'], &[',', ';', ':', '—'], &[' ']];
+
+    let mut chunks = Vec::new();
+    let mut rest = phonemes.trim();
+    // Byte offset of the first token exceeding the limit
+    while let Some((limit, _)) = rest
+        .char_indices()
+        .filter(|(_, c)| KokoroTokenizer::is_known(*c))
+        .nth(max_tokens)
+    {
+        let window = &rest[..limit];
+        let split = boundaries
+            .iter()
+            .find_map(|chars| {
+                window
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| chars.contains(c))
+                    .map(|(idx, c)| idx + c.len_utf8())
+            })
+            .unwrap_or(limit);
+
+        chunks.push(rest[..split].trim());
+        rest = rest[split..].trim();
+    }
+
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_phonemes_test() {
+        let phonemes = "ðə kˈæt. ɪt sˈæt, ɔn ðə mˈæt";
+
+        assert_eq!(split_phonemes(phonemes, 100), vec![phonemes]);
+        assert_eq!(
+            split_phonemes(phonemes, 12),
+            vec!["ðə kˈæt.", "ɪt sˈæt,", "ɔn ðə mˈæt"]
+        );
+        assert_eq!(
+            split_phonemes(phonemes, 7),
+            vec!["ðə", "kˈæt.", "ɪt", "sˈæt,", "ɔn ðə", "mˈæt"]
+        );
+        assert_eq!(split_phonemes("", 20), vec![""]);
+    }
+
+    #[test]
+    fn split_phonemes_without_boundaries_test() {
+        assert_eq!(split_phonemes("abcdefhi", 3), vec!["abc", "def", "hi"]);
+    }
+
+    #[test]
+    fn voice_style_limit_test() {
+        // The last style must be reachable by the longest allowed input
+        let longest = "a".repeat(KOKORO_STYLE_NUM - 3);
+        let tokens = KokoroTokenizer::tokenize(&longest).len();
+
+        assert_eq!(KokoroVoice::AF_BELLA.style(tokens).len(), KOKORO_STYLE_DIM);
+    }
+
+    #[test]
+    fn voice_blend_test() {
+        let blend: VoiceBlend = "af_bella*3 + bf_emma".parse().unwrap();
+        assert_eq!(
+            blend.voices(),
+            &[(KokoroVoice::AF_BELLA, 0.75), (KokoroVoice::BF_EMMA, 0.25)]
+        );
+
+        let expected: Vec<f32> = KokoroVoice::AF_BELLA
+            .style(10)
+            .iter()
+            .zip(KokoroVoice::BF_EMMA.style(10))
+            .map(|(a, b)| 0.75 * a + 0.25 * b)
+            .collect();
+        assert_eq!(Voice::style(&blend, 10).as_ref(), expected.as_slice());
+        assert_eq!(blend.language(), Language::EnUs);
+
+        assert!("af_bella+nobody".parse::<VoiceBlend>().is_err());
+        assert!("af_bella*0".parse::<VoiceBlend>().is_err());
+        assert!("".parse::<VoiceBlend>().is_err());
     }
 }
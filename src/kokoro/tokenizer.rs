@@ -1,62 +1,44 @@
 use phf::phf_map;
-use regex::Regex;
 
-/// Regex string used for cleaning the input.
-/// TODO: Lazy or compile time compilation of regex
-static CLEANING_REGEX: &str = r#"[^ !\"$',.:;?A-Za-z\u00a1\u00ab\u00bb\u00bf\u00e6\u00e7\u00f0\u00f8\u0127\u014b\u0153\u01c0-\u01c3\u0250-\u0268\u026a-\u0276\u0278-\u027b\u027d\u027e\u0280-\u0284\u0288-\u0292\u0294\u0295\u0298\u0299\u029b-\u029d\u029f\u02a1\u02a2\u02a4\u02a7\u02b0-\u02b2\u02b4\u02b7\u02bc\u02c8\u02cc\u02d0\u02d1\u02de\u02e0\u02e4\u0329\u03b2\u03b8\u03c7\u1d7b\u2014\u201c\u201d\u2026\u2191-\u2193\u2197\u2198\u2c71]"#;
-
-/// Mapping from input characters to token ids of Kokoro.
+/// Mapping from input characters to token ids of Kokoro v1.0, as in the `vocab` of its `config.json`. The id 0
+/// is used for padding.
 static VOCAB: phf::Map<char, u32> = phf_map! {
-    '$' => 0,
     ';' => 1,
     ':' => 2,
     ',' => 3,
     '.' => 4,
     '!' => 5,
     '?' => 6,
-    '\u{00a1}' => 7,
-    '\u{00bf}' => 8,
     '\u{2014}' => 9,
     '\u{2026}' => 10,
     '"' => 11,
-    '\u{00ab}' => 12,
-    '\u{00bb}' => 13,
+    '(' => 12,
+    ')' => 13,
     '\u{201c}' => 14,
     '\u{201d}' => 15,
     ' ' => 16,
-    'A' => 17,
-    'B' => 18,
-    'C' => 19,
-    'D' => 20,
-    'E' => 21,
-    'F' => 22,
-    'G' => 23,
-    'H' => 24,
+    '\u{0303}' => 17,
+    '\u{02a3}' => 18,
+    '\u{02a5}' => 19,
+    '\u{02a6}' => 20,
+    '\u{02a8}' => 21,
+    '\u{1d5d}' => 22,
+    '\u{ab67}' => 23,
+    'A' => 24,
     'I' => 25,
-    'J' => 26,
-    'K' => 27,
-    'L' => 28,
-    'M' => 29,
-    'N' => 30,
     'O' => 31,
-    'P' => 32,
     'Q' => 33,
-    'R' => 34,
     'S' => 35,
     'T' => 36,
-    'U' => 37,
-    'V' => 38,
     'W' => 39,
-    'X' => 40,
     'Y' => 41,
-    'Z' => 42,
+    '\u{1d4a}' => 42,
     'a' => 43,
     'b' => 44,
     'c' => 45,
     'd' => 46,
     'e' => 47,
     'f' => 48,
-    'g' => 49,
     'h' => 50,
     'i' => 51,
     'j' => 52,
@@ -80,43 +62,23 @@ static VOCAB: phf::Map<char, u32> = phf_map! {
     '\u{0250}' => 70,
     '\u{0252}' => 71,
     '\u{00e6}' => 72,
-    '\u{0253}' => 73,
-    '\u{0299}' => 74,
     '\u{03b2}' => 75,
     '\u{0254}' => 76,
     '\u{0255}' => 77,
     '\u{00e7}' => 78,
-    '\u{0257}' => 79,
     '\u{0256}' => 80,
     '\u{00f0}' => 81,
     '\u{02a4}' => 82,
     '\u{0259}' => 83,
-    '\u{0258}' => 84,
     '\u{025a}' => 85,
     '\u{025b}' => 86,
     '\u{025c}' => 87,
-    '\u{025d}' => 88,
-    '\u{025e}' => 89,
     '\u{025f}' => 90,
-    '\u{0284}' => 91,
     '\u{0261}' => 92,
-    '\u{0260}' => 93,
-    '\u{0262}' => 94,
-    '\u{029b}' => 95,
-    '\u{0266}' => 96,
-    '\u{0267}' => 97,
-    '\u{0127}' => 98,
     '\u{0265}' => 99,
-    '\u{029c}' => 100,
     '\u{0268}' => 101,
     '\u{026a}' => 102,
     '\u{029d}' => 103,
-    '\u{026d}' => 104,
-    '\u{026c}' => 105,
-    '\u{026b}' => 106,
-    '\u{026e}' => 107,
-    '\u{029f}' => 108,
-    '\u{0271}' => 109,
     '\u{026f}' => 110,
     '\u{0270}' => 111,
     '\u{014b}' => 112,
@@ -124,97 +86,56 @@ static VOCAB: phf::Map<char, u32> = phf_map! {
     '\u{0272}' => 114,
     '\u{0274}' => 115,
     '\u{00f8}' => 116,
-    '\u{0275}' => 117,
     '\u{0278}' => 118,
     '\u{03b8}' => 119,
     '\u{0153}' => 120,
-    '\u{0276}' => 121,
-    '\u{0298}' => 122,
     '\u{0279}' => 123,
-    '\u{027a}' => 124,
     '\u{027e}' => 125,
     '\u{027b}' => 126,
-    '\u{0280}' => 127,
     '\u{0281}' => 128,
     '\u{027d}' => 129,
     '\u{0282}' => 130,
     '\u{0283}' => 131,
     '\u{0288}' => 132,
     '\u{02a7}' => 133,
-    '\u{0289}' => 134,
     '\u{028a}' => 135,
     '\u{028b}' => 136,
-    '\u{2c71}' => 137,
     '\u{028c}' => 138,
     '\u{0263}' => 139,
     '\u{0264}' => 140,
-    '\u{028d}' => 141,
     '\u{03c7}' => 142,
     '\u{028e}' => 143,
-    '\u{028f}' => 144,
-    '\u{0291}' => 145,
-    '\u{0290}' => 146,
     '\u{0292}' => 147,
     '\u{0294}' => 148,
-    '\u{02a1}' => 149,
-    '\u{0295}' => 150,
-    '\u{02a2}' => 151,
-    '\u{01c0}' => 152,
-    '\u{01c1}' => 153,
-    '\u{01c2}' => 154,
-    '\u{01c3}' => 155,
     '\u{02c8}' => 156,
     '\u{02cc}' => 157,
     '\u{02d0}' => 158,
-    '\u{02d1}' => 159,
-    '\u{02bc}' => 160,
-    '\u{02b4}' => 161,
     '\u{02b0}' => 162,
-    '\u{02b1}' => 163,
     '\u{02b2}' => 164,
-    '\u{02b7}' => 165,
-    '\u{02e0}' => 166,
-    '\u{02e4}' => 167,
-    '\u{02de}' => 168,
     '\u{2193}' => 169,
-    '\u{2191}' => 170,
     '\u{2192}' => 171,
     '\u{2197}' => 172,
     '\u{2198}' => 173,
-    '\u{0329}' => 175,
-    '\'' => 176,
-    '\u{1d7b}' => 177
-
+    '\u{1d7b}' => 177,
 };
 
 pub struct KokoroTokenizer;
 impl KokoroTokenizer {
+    /// Check whether a character is part of the vocabulary, i.e. whether it is kept by the tokenizer.
+    pub fn is_known(c: char) -> bool {
+        VOCAB.contains_key(&c)
+    }
+
     /// Reimplementation of the Kokoro tokenizer, as described in
     /// [onnx-community/Kokoro-82M-v1.0-ONNX](https://huggingface.co/onnx-community/Kokoro-82M-v1.0-ONNX/blob/main/tokenizer.json).
     /// Step:
-    ///     1. Clean input by removing all matches to a regex
-    ///     2. Split into single characters
-    ///     3. Map characters to token ids
-    ///     4. Insert a single padding token (0) at start and end
-    ///
-    /// # Panics
-    /// This function can panic for two reasons:
-    ///     - Regex compilation can fail, which should not happen, as it has been tested in practice.
-    ///     - Mapping a character to a token id fails. This should not happen, as the regex in the first step
-    ///       should in theory remove all invalid chars, so that only those contained in the vocabulary remain.
+    ///     1. Split into single characters
+    ///     2. Map characters to token ids, dropping those not contained in the vocabulary
+    ///     3. Insert a single padding token (0) at start and end
     pub fn tokenize(text: &str) -> Vec<u32> {
-        let re = Regex::new(CLEANING_REGEX)
-            .expect("Regex for cleaning Kokoro tokenizer input is not valid.");
-
-        let mut unpadded_result: Vec<u32> = re
-            .replace_all(text, "")
-            .to_string()
+        let mut unpadded_result: Vec<u32> = text
             .chars()
-            .map(|c| {
-                *VOCAB
-                    .get(&c)
-                    .expect("Tokenization for Kokoro failed, char >>{c}<< not found in vocabulary.")
-            })
+            .filter_map(|c| VOCAB.get(&c).copied())
             .collect();
 
         unpadded_result.insert(0, 0);
@@ -230,9 +151,19 @@ mod tests {
 
     #[test]
     fn basic_tokenization_test() {
-        let test_input = "Hello";
+        let test_input = "hello";
         // Validated result with JS reimplementation of Kokoro tokenizer
-        let expected: Vec<u32> = vec![0, 24, 47, 54, 54, 57, 0];
+        let expected: Vec<u32> = vec![0, 50, 47, 54, 54, 57, 0];
+
+        let result = KokoroTokenizer::tokenize(test_input);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn misaki_symbols_test() {
+        // Diphthongs and affricates of misaki
+        let test_input = "AIOQWYᵊʣʥʦʨ";
+        let expected: Vec<u32> = vec![0, 24, 25, 31, 33, 39, 41, 42, 18, 19, 20, 21, 0];
 
         let result = KokoroTokenizer::tokenize(test_input);
         assert_eq!(result, expected);
@@ -275,4 +206,30 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    #[cfg_attr(
+        not(feature = "download-data"),
+        ignore = "needs the config.json downloaded with the feature download-data"
+    )]
+    fn vocab_matches_config_test() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/config.json");
+        let config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let vocab = config["vocab"].as_object().unwrap();
+
+        assert_eq!(vocab.len(), VOCAB.len());
+        for (symbol, id) in vocab {
+            let mut chars = symbol.chars();
+            let c = chars.next().unwrap();
+            assert!(
+                chars.next().is_none(),
+                "{symbol:?} is not a single character"
+            );
+            assert_eq!(
+                VOCAB.get(&c).map(|id| *id as u64),
+                id.as_u64(),
+                "id of {symbol:?}"
+            );
+        }
+    }
 }
@@ -1,5 +1,19 @@
 #![doc = include_str!("../README.md")]
+pub mod book;
+pub mod dialogue;
+pub mod dsp;
+pub mod estimate;
 mod kokoro;
+pub mod phonemes;
+pub mod phonemizer;
+pub mod ssml;
+pub mod subtitles;
+pub mod text;
+pub mod timings;
 pub mod utils;
 
-pub use kokoro::{Kokoro, KokoroVoice};
+pub use kokoro::{
+    Kokoro, KokoroVoice, Language, MAX_SPEED, MIN_SPEED, SAMPLE_RATE, TextOptions, Voice,
+    VoiceBlend,
+};
+pub use phonemizer::Phonemizer;
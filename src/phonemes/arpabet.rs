@@ -0,0 +1,182 @@
+//! Conversion of [ARPAbet](https://en.wikipedia.org/wiki/ARPABET), as used by CMUdict, into IPA.
+
+use anyhow::{Result, bail};
+
+/// ARPAbet vowels and their IPA, unstressed and stressed. Only `AH` and `ER` change with stress.
+static VOWELS: [(&str, &str, &str); 20] = [
+    ("AA", "ɑ", "ɑ"),
+    ("AE", "æ", "æ"),
+    ("AH", "ə", "ʌ"),
+    ("AO", "ɔ", "ɔ"),
+    ("AW", "aʊ", "aʊ"),
+    ("AX", "ə", "ə"),
+    ("AXR", "ɚ", "ɚ"),
+    ("AY", "aɪ", "aɪ"),
+    ("EH", "ɛ", "ɛ"),
+    ("ER", "ɚ", "ɜɹ"),
+    ("EY", "eɪ", "eɪ"),
+    ("IH", "ɪ", "ɪ"),
+    ("IX", "ᵻ", "ᵻ"),
+    ("IY", "i", "i"),
+    ("OW", "oʊ", "oʊ"),
+    ("OY", "ɔɪ", "ɔɪ"),
+    ("UH", "ʊ", "ʊ"),
+    ("UW", "u", "u"),
+    ("UX", "u", "u"), // Kokoro doesn't know ʉ
+    ("EL", "əl", "əl"),
+];
+
+/// ARPAbet consonants and their IPA.
+static CONSONANTS: [(&str, &str); 29] = [
+    ("B", "b"),
+    ("CH", "ʧ"),
+    ("D", "d"),
+    ("DH", "ð"),
+    ("DX", "ɾ"),
+    ("EM", "əm"),
+    ("EN", "ən"),
+    ("F", "f"),
+    ("G", "ɡ"),
+    ("H", "h"),
+    ("HH", "h"),
+    ("JH", "ʤ"),
+    ("K", "k"),
+    ("L", "l"),
+    ("M", "m"),
+    ("N", "n"),
+    ("NG", "ŋ"),
+    ("NX", "ɾ"),
+    ("P", "p"),
+    ("Q", "ʔ"),
+    ("R", "ɹ"),
+    ("S", "s"),
+    ("SH", "ʃ"),
+    ("T", "t"),
+    ("TH", "θ"),
+    ("V", "v"),
+    ("W", "w"),
+    ("WH", "w"), // Kokoro doesn't know ʍ
+    ("Y", "j"),
+];
+
+/// Punctuation that is kept and ends the current word.
+fn is_punctuation(c: char) -> bool {
+    matches!(c, ',' | '.' | '!' | '?' | ';' | ':' | '—' | '…')
+}
+
+/// Convert ARPAbet into IPA that can be passed to [`Kokoro::phonemes2audio`](crate::Kokoro::phonemes2audio).
+///
+/// Phonemes are separated by whitespace and case is ignored. Words are separated by `|` or punctuation, like
+/// `HH AH0 L OW1 , | W ER1 L D .`. Stress digits on vowels become `ˈ` (1) or `ˌ` (2) in front of the vowel,
+/// 0 marks an unstressed vowel. Lines are converted separately, so paragraphs are kept.
+pub fn to_ipa(arpabet: &str) -> Result<String> {
+    arpabet
+        .lines()
+        .map(line_to_ipa)
+        .collect::<Result<Vec<_>>>()
+        .map(|lines| lines.join("\n"))
+}
+
+/// Convert a single line of ARPAbet.
+fn line_to_ipa(line: &str) -> Result<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+
+    for token in line.replace('|', " | ").split_whitespace() {
+        if token != "|" {
+            let phoneme = token.trim_end_matches(is_punctuation);
+            if !phoneme.is_empty() {
+                word.push_str(&phoneme_to_ipa(phoneme)?);
+            }
+
+            let punctuation = &token[phoneme.len()..];
+            if punctuation.is_empty() {
+                continue;
+            }
+            // Punctuation standing on its own belongs to the previous word
+            match words.last_mut() {
+                Some(last) if word.is_empty() => last.push_str(punctuation),
+                _ => word.push_str(punctuation),
+            }
+        }
+
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    Ok(words.join(" "))
+}
+
+/// Convert a single ARPAbet phoneme, with an optional stress digit.
+fn phoneme_to_ipa(phoneme: &str) -> Result<String> {
+    let upper = phoneme.to_uppercase();
+    let (symbol, stress) = match upper.strip_suffix(['0', '1', '2']) {
+        Some(symbol) => (symbol, upper.chars().last()),
+        None => (upper.as_str(), None),
+    };
+
+    if let Some((_, unstressed, stressed)) = VOWELS.iter().find(|(arpabet, ..)| *arpabet == symbol)
+    {
+        return Ok(match stress {
+            Some('1') => format!("ˈ{stressed}"),
+            Some('2') => format!("ˌ{stressed}"),
+            _ => unstressed.to_string(),
+        });
+    }
+
+    match CONSONANTS.iter().find(|(arpabet, _)| *arpabet == symbol) {
+        Some(_) if stress.is_some() => {
+            bail!("ARPAbet consonant '{phoneme}' can't carry stress")
+        }
+        Some((_, ipa)) => Ok(ipa.to_string()),
+        None => bail!("Unknown ARPAbet phoneme '{phoneme}'"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kokoro::tokenizer::KokoroTokenizer;
+
+    #[test]
+    fn to_ipa_test() -> Result<()> {
+        let cases = [
+            ("HH AH0 L OW1 | W ER1 L D", "həlˈoʊ wˈɜɹld"),
+            ("hh ah0 l ow1, w er1 l d.", "həlˈoʊ, wˈɜɹld."),
+            ("HH AH0 L OW1 , | W ER1 L D .", "həlˈoʊ, wˈɜɹld."),
+            ("B AH1 T ER0|K AH2 P", "bˈʌtɚ kˌʌp"),
+            ("JH AH1 JH | CH ER1 CH ?", "ʤˈʌʤ ʧˈɜɹʧ?"),
+            ("AH0 B AW1 T\n\nDH IY0", "əbˈaʊt\n\nði"),
+            ("", ""),
+        ];
+
+        for (arpabet, expected) in cases {
+            assert_eq!(to_ipa(arpabet)?, expected, "{arpabet:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_arpabet_test() {
+        assert!(to_ipa("HH AH0 L OW1 XX").is_err());
+        assert!(to_ipa("K1 AE1 T").is_err());
+        assert!(to_ipa("K AE3 T").is_err());
+    }
+
+    #[test]
+    fn ipa_is_known_test() {
+        let symbols = VOWELS
+            .iter()
+            .flat_map(|(_, unstressed, stressed)| [*unstressed, *stressed])
+            .chain(CONSONANTS.iter().map(|(_, ipa)| *ipa));
+
+        for symbol in symbols {
+            assert!(symbol.chars().all(KokoroTokenizer::is_known), "{symbol:?}");
+        }
+    }
+}
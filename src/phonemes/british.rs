@@ -0,0 +1,83 @@
+//! Conversion of American English phonemes into British English (Received Pronunciation).
+
+use super::{is_vowel, to_kokoro};
+
+/// Convert American English phonemes, in IPA or misaki, into British ones in misaki's notation.
+///
+/// This covers the differences that can be told from the phonemes alone: the goat vowel `O` becomes `Q`, `ɹ`
+/// is dropped where it isn't followed by a vowel, lengthening the vowel before it (`ɑɹ` becomes `ɑː`, `ɪɹ`
+/// becomes `ɪə`), r-colored vowels lose their color, flapped `t` is restored and the lot vowel `ɑ` becomes `ɒ`.
+/// Words with the bath or palm vowel can't be told apart from others by their phonemes and are handled by
+/// [`BritishPhonemizer`](crate::phonemizer::BritishPhonemizer).
+pub fn from_american(phonemes: &str) -> String {
+    let chars: Vec<char> = to_kokoro(phonemes).chars().collect();
+    // Next symbol that isn't a stress mark, which stands before the vowel of a syllable
+    let next = |i: usize| {
+        chars[i + 1..]
+            .iter()
+            .copied()
+            .find(|c| !matches!(c, 'ˈ' | 'ˌ'))
+    };
+    let before_vowel = |i: usize| next(i).is_some_and(is_vowel);
+
+    let mut british = String::with_capacity(chars.len() * 2);
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            'O' => british.push('Q'),
+            'ɑ' => match chars.get(i + 1) {
+                Some('ː') => british.push('ɑ'),
+                Some('ɹ') if !before_vowel(i + 1) => british.push('ɑ'),
+                _ => british.push('ɒ'),
+            },
+            'ɚ' if before_vowel(i) => british.push_str("əɹ"),
+            'ɚ' => british.push('ə'),
+            'ɜ' if chars.get(i + 1) == Some(&'ɹ') && before_vowel(i + 1) => {
+                british.push_str("ɜː")
+            }
+            'ɾ' => british.push('t'),
+            'ɹ' if !before_vowel(i) => match british.chars().last() {
+                Some('ɑ' | 'ɔ' | 'ɜ') => british.push('ː'),
+                Some('ɪ' | 'ʊ' | 'ɛ' | 'e' | 'i' | 'u' | 'A' | 'I' | 'W' | 'Y') => {
+                    british.push('ə')
+                }
+                _ => {}
+            },
+            _ => british.push(c),
+        }
+    }
+
+    british
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kokoro::tokenizer::KokoroTokenizer;
+
+    #[test]
+    fn from_american_test() {
+        let cases = [
+            ("həlˈO wˈɜɹld", "həlˈQ wˈɜːld"),
+            ("hˈoʊm ˈaʊɹ", "hˈQm ˈWə"),
+            ("kˈɑɹ pˈɑɹk", "kˈɑː pˈɑːk"),
+            ("hˈɪɹ ðˈɛɹ, fˈIɹ", "hˈɪə ðˈɛə, fˈIə"),
+            ("fˈɔɹ mˈɔɹnɪŋ", "fˈɔː mˈɔːnɪŋ"),
+            ("wˈɔɾɚ bˈɛɾɚ", "wˈɔtə bˈɛtə"),
+            ("vˈɛɹi sˈɑɹi", "vˈɛɹi sˈɒɹi"),
+            ("hˈɑt stˈɑp", "hˈɒt stˈɒp"),
+            ("kəɹˈɛkt", "kəɹˈɛkt"),
+            ("bɝd stˈɝɪŋ", "bɜːd stˈɜːɹɪŋ"),
+            ("", ""),
+        ];
+
+        for (american, expected) in cases {
+            assert_eq!(from_american(american), expected, "{american:?}");
+        }
+    }
+
+    #[test]
+    fn british_is_known_test() {
+        let british = from_american("ɚ ɝ ɾ ɑ ɑɹ ɔɹ ɪɹ oʊ");
+        assert!(british.chars().all(KokoroTokenizer::is_known));
+    }
+}
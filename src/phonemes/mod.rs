@@ -0,0 +1,158 @@
+//! Conversion between phoneme notations and the symbols Kokoro reads.
+
+pub mod arpabet;
+pub mod british;
+pub mod xsampa;
+
+/// IPA and the shorthands of [misaki](https://github.com/hexgrad/misaki), the phonemizer Kokoro v1.0 was trained
+/// with. Kokoro's vocabulary has tokens for these, so e.g. `eɪ` has to be read as `A` to sound like "ay".
+static SHORTHANDS: [(&str, char); 10] = [
+    ("eɪ", 'A'), // face
+    ("aɪ", 'I'), // price
+    ("oʊ", 'O'), // goat, American
+    ("əʊ", 'Q'), // goat, British
+    ("aʊ", 'W'), // mouth
+    ("ɔɪ", 'Y'), // choice
+    ("dʒ", 'ʤ'),
+    ("tʃ", 'ʧ'),
+    ("dʑ", 'ʥ'),
+    ("tɕ", 'ʨ'),
+];
+
+/// Affricates that are only written as ligatures when IPA joins them with a tie bar, as e.g. `ts` in "cats" are
+/// two sounds.
+static TIED_AFFRICATES: [(char, char, char); 2] = [('d', 'z', 'ʣ'), ('t', 's', 'ʦ')];
+
+/// Tie bars joining affricates like `t͡ʃ`, above and below.
+static TIE_BARS: [char; 2] = ['\u{0361}', '\u{035c}'];
+
+/// IPA symbols missing from Kokoro's vocabulary, and how misaki writes them.
+static SUBSTITUTES: [(char, &str); 2] = [
+    ('g', "ɡ"),  // Latin g instead of the IPA script g
+    ('ɝ', "ɜɹ"), // r-colored vowel of "bird"
+];
+
+/// Map phonemes from misaki or IPA onto the symbols Kokoro was trained on.
+///
+/// Diphthongs and affricates are joined into misaki's shorthands and tie bars are removed. Symbols that already
+/// match are kept, so mapping twice doesn't change the result.
+pub fn to_kokoro(phonemes: &str) -> String {
+    let mut phonemes = phonemes.to_owned();
+    for (first, second, ligature) in TIED_AFFRICATES {
+        for tie_bar in TIE_BARS {
+            phonemes =
+                phonemes.replace(&format!("{first}{tie_bar}{second}"), &ligature.to_string());
+        }
+    }
+    let mut phonemes = phonemes.replace(TIE_BARS, "");
+    for (c, substitute) in SUBSTITUTES {
+        phonemes = phonemes.replace(c, substitute);
+    }
+    for (ipa, shorthand) in SHORTHANDS {
+        phonemes = phonemes.replace(ipa, &shorthand.to_string());
+    }
+
+    phonemes
+}
+
+/// Vowels, in IPA, the symbols Kokoro reads and misaki's shorthands.
+pub(crate) fn is_vowel(c: char) -> bool {
+    matches!(
+        c,
+        'A' | 'I'
+            | 'O'
+            | 'Q'
+            | 'W'
+            | 'Y'
+            | 'ᵊ'
+            | 'a'
+            | 'e'
+            | 'i'
+            | 'o'
+            | 'u'
+            | 'y'
+            | 'æ'
+            | 'ɐ'
+            | 'ɑ'
+            | 'ɒ'
+            | 'ɔ'
+            | 'ə'
+            | 'ɚ'
+            | 'ɛ'
+            | 'ɜ'
+            | 'ɝ'
+            | 'ɪ'
+            | 'ʊ'
+            | 'ʌ'
+            | 'ᵻ'
+    )
+}
+
+/// How much a word is emphasized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emphasis {
+    /// Read without primary stress, like a function word.
+    Reduced,
+    /// Every word gets a primary stress.
+    Moderate,
+    /// Every stressed syllable gets a primary stress, and the words are set off by pauses, see
+    /// [`overrides`](crate::text::overrides).
+    Strong,
+}
+
+/// Emphasize words by changing their stress marks. Words without a stress mark get a primary stress on their first
+/// vowel.
+pub fn emphasize(phonemes: &str, emphasis: Emphasis) -> String {
+    let words = phonemes.split(' ').map(|word| match emphasis {
+        Emphasis::Reduced => word.replace('ˈ', "ˌ"),
+        Emphasis::Strong if word.contains('ˌ') => word.replace('ˌ', "ˈ"),
+        _ if word.contains('ˈ') => word.to_owned(),
+        _ if word.contains('ˌ') => word.replacen('ˌ', "ˈ", 1),
+        _ => match word.find(is_vowel) {
+            Some(i) => format!("{}ˈ{}", &word[..i], &word[i..]),
+            None => word.to_owned(),
+        },
+    });
+
+    words.collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_kokoro_test() {
+        let cases = [
+            ("həlˈoʊ wˈɝld", "həlˈO wˈɜɹld"),
+            ("ɡʊdbˈaɪ", "ɡʊdbˈI"),
+            ("nˈeɪ, hˈaʊ ɪz ðə bˈɔɪ?", "nˈA, hˈW ɪz ðə bˈY?"),
+            ("hˈəʊm", "hˈQm"),
+            ("bˈʌʔᵊn", "bˈʌʔᵊn"),
+            ("ʧˈɜɹʧ ʤˈʌʤ", "ʧˈɜɹʧ ʤˈʌʤ"),
+            ("tʃˈɜːtʃ d͡ʒˈʌdʒ", "ʧˈɜːʧ ʤˈʌʤ"),
+            ("t͡sˈu gˈʊd kˈæts", "ʦˈu ɡˈʊd kˈæts"),
+            ("tɕˈi d͜zˈo", "ʨˈi ʣˈo"),
+            ("", ""),
+        ];
+
+        for (phonemes, expected) in cases {
+            assert_eq!(to_kokoro(phonemes), expected, "{phonemes:?}");
+            assert_eq!(to_kokoro(expected), expected, "{expected:?}");
+        }
+    }
+
+    #[test]
+    fn emphasize_test() {
+        let phonemes = "ðə kˈæt sˌæt dˌWn";
+
+        assert_eq!(
+            emphasize(phonemes, Emphasis::Moderate),
+            "ðˈə kˈæt sˈæt dˈWn"
+        );
+        assert_eq!(emphasize(phonemes, Emphasis::Strong), "ðˈə kˈæt sˈæt dˈWn");
+        assert_eq!(emphasize("ˌʌndɚstˈænd", Emphasis::Strong), "ˈʌndɚstˈænd");
+        assert_eq!(emphasize(phonemes, Emphasis::Reduced), "ðə kˌæt sˌæt dˌWn");
+        assert_eq!(emphasize("", Emphasis::Moderate), "");
+    }
+}
@@ -0,0 +1,195 @@
+//! Conversion of [X-SAMPA](https://en.wikipedia.org/wiki/X-SAMPA) into IPA, which can be typed on any keyboard.
+
+use anyhow::{Result, bail};
+
+/// X-SAMPA symbols and their IPA. Only symbols that Kokoro knows after [`to_kokoro`](super::to_kokoro) are
+/// included, so that unknown ones fail instead of being dropped by the tokenizer.
+static SYMBOLS: [(&str, &str); 73] = [
+    // Vowels
+    ("a", "a"),
+    ("e", "e"),
+    ("i", "i"),
+    ("o", "o"),
+    ("u", "u"),
+    ("y", "y"),
+    ("A", "ɑ"),
+    ("{", "æ"),
+    ("6", "ɐ"),
+    ("Q", "ɒ"),
+    ("E", "ɛ"),
+    ("@", "ə"),
+    ("@`", "ɚ"),
+    ("3", "ɜ"),
+    ("3`", "ɝ"),
+    ("I", "ɪ"),
+    ("I\\", "ᵻ"),
+    ("O", "ɔ"),
+    ("2", "ø"),
+    ("9", "œ"),
+    ("U", "ʊ"),
+    ("V", "ʌ"),
+    ("1", "ɨ"),
+    ("7", "ɤ"),
+    ("M", "ɯ"),
+    // Plosives
+    ("b", "b"),
+    ("d", "d"),
+    ("g", "ɡ"),
+    ("k", "k"),
+    ("p", "p"),
+    ("q", "q"),
+    ("t", "t"),
+    ("c", "c"),
+    ("t`", "ʈ"),
+    ("d`", "ɖ"),
+    ("J\\", "ɟ"),
+    ("?", "ʔ"),
+    // Nasals
+    ("m", "m"),
+    ("n", "n"),
+    ("n`", "ɳ"),
+    ("J", "ɲ"),
+    ("N", "ŋ"),
+    ("N\\", "ɴ"),
+    // Trills, taps and flaps
+    ("r", "r"),
+    ("4", "ɾ"),
+    ("r`", "ɽ"),
+    // Fricatives
+    ("f", "f"),
+    ("v", "v"),
+    ("s", "s"),
+    ("z", "z"),
+    ("x", "x"),
+    ("h", "h"),
+    ("B", "β"),
+    ("T", "θ"),
+    ("D", "ð"),
+    ("S", "ʃ"),
+    ("Z", "ʒ"),
+    ("s`", "ʂ"),
+    ("s\\", "ɕ"),
+    ("C", "ç"),
+    ("j\\", "ʝ"),
+    ("G", "ɣ"),
+    ("X", "χ"),
+    ("R", "ʁ"),
+    // Approximants
+    ("w", "w"),
+    ("j", "j"),
+    ("l", "l"),
+    ("P", "ʋ"),
+    ("r\\", "ɹ"),
+    ("r\\`", "ɻ"),
+    ("M\\", "ɰ"),
+    ("H", "ɥ"),
+    ("L", "ʎ"),
+];
+
+/// X-SAMPA suprasegmentals and diacritics and their IPA.
+static MARKS: [(&str, &str); 7] = [
+    ("\"", "ˈ"),
+    ("%", "ˌ"),
+    (":", "ː"),
+    ("~", "\u{0303}"),
+    ("_h", "ʰ"),
+    ("_j", "ʲ"),
+    ("'", "ʲ"),
+];
+
+/// Punctuation that is kept as it is.
+fn is_punctuation(c: char) -> bool {
+    matches!(c, ',' | '!' | ';' | '—' | '…')
+}
+
+/// Convert X-SAMPA into IPA that can be passed to [`Kokoro::phonemes2audio`](crate::Kokoro::phonemes2audio).
+///
+/// Symbols are matched greedily, so `tS` is read as `tʃ` and `r\` as `ɹ`. Since `.` and `?` are also X-SAMPA
+/// symbols, they are only read as punctuation at the end of a word, otherwise as a syllable break (which is
+/// dropped) and a glottal stop. Whitespace and other punctuation is kept.
+///
+/// # Errors
+/// Fails on symbols that have no IPA counterpart Kokoro knows, like ejectives (`_>`).
+pub fn to_ipa(xsampa: &str) -> Result<String> {
+    let mut ipa = String::with_capacity(xsampa.len());
+
+    let mut rest = xsampa;
+    while let Some(c) = rest.chars().next() {
+        let word_end = rest[c.len_utf8()..]
+            .chars()
+            .next()
+            .is_none_or(|next| next.is_whitespace() || is_punctuation(next));
+
+        let matched = if c.is_whitespace() || is_punctuation(c) || (c == '?' && word_end) {
+            ipa.push(c);
+            c.len_utf8()
+        } else if c == '.' {
+            if word_end {
+                ipa.push(c);
+            }
+            1
+        } else {
+            match SYMBOLS
+                .iter()
+                .chain(&MARKS)
+                .filter(|(symbol, _)| rest.starts_with(symbol))
+                .max_by_key(|(symbol, _)| symbol.len())
+            {
+                Some((symbol, symbol_ipa)) => {
+                    ipa.push_str(symbol_ipa);
+                    symbol.len()
+                }
+                None => bail!(
+                    "X-SAMPA symbol '{c}' at position {} can't be converted into phonemes Kokoro knows",
+                    xsampa[..xsampa.len() - rest.len()].chars().count() + 1
+                ),
+            }
+        };
+
+        rest = &rest[matched..];
+    }
+
+    Ok(ipa)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{kokoro::tokenizer::KokoroTokenizer, phonemes::to_kokoro};
+
+    #[test]
+    fn to_ipa_test() -> Result<()> {
+        let cases = [
+            ("h@\"l@U w3:ld", "həˈləʊ wɜːld"),
+            ("\"tS3:tS, \"dZVdZ!", "ˈtʃɜːtʃ, ˈdʒʌdʒ!"),
+            ("\"r\\Vn %{n.d@`.st{nd", "ˈɹʌn ˌændɚstænd"),
+            ("\"bV?@n \"b{~?", "ˈbʌʔən ˈbæ\u{0303}?"),
+            ("t_h\"Ai. x", "tʰˈɑi. x"),
+            ("", ""),
+        ];
+
+        for (xsampa, expected) in cases {
+            assert_eq!(to_ipa(xsampa)?, expected, "{xsampa:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_xsampa_test() {
+        assert!(to_ipa("p_>").is_err());
+        assert!(to_ipa("n=").is_err());
+        assert!(to_ipa("x\\").is_err());
+        assert!(to_ipa("U\\").is_err());
+        assert!(to_ipa("k_0").is_err());
+        assert!(to_ipa("h@l@ä").is_err());
+    }
+
+    #[test]
+    fn ipa_is_known_test() {
+        for (symbol, ipa) in SYMBOLS.iter().chain(&MARKS) {
+            let kokoro = to_kokoro(ipa);
+            assert!(kokoro.chars().all(KokoroTokenizer::is_known), "{symbol:?}");
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Conversion of text into the phonemes Kokoro is synthesizing.
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use anyhow::{Result, bail};
+use regex::Regex;
+
+use crate::{Language, phonemes::british, text::overrides};
+
+/// Words with the bath vowel (`ɑː` instead of `æ`) or the palm vowel (`ɑː` instead of `ɑ`) in British English,
+/// and their inflections.
+static BROAD_A_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(after|answer|ask|aunt|banana|basket|bath|branch|brass|calm|can't|castle|chance|class|command|dance|demand|disaster|drama|example|fast|father|france|glance|glass|grass|half|last|laugh|master|nasty|palm|pass|past|path|plant|rather|shan't|spa|staff|task|vast)(s|es|ed|ing|er|ers)?\b",
+    )
+    .expect("Regex for broad a words is not valid.")
+});
+
+/// Converts text into phonemes that can be passed to [`Kokoro::phonemes2audio`](crate::Kokoro::phonemes2audio).
+///
+/// Implement this to use a custom phonemizer. Closures taking text and returning phonemes implement it as well.
+pub trait Phonemizer {
+    /// Convert text into phonemes.
+    fn phonemize(&self, text: &str) -> Result<String>;
+}
+
+impl<F: Fn(&str) -> Result<String>> Phonemizer for F {
+    fn phonemize(&self, text: &str) -> Result<String> {
+        self(text)
+    }
+}
+
+/// Phonemizer for British English, built on a phonemizer for American English whose phonemes are converted with
+/// [`british::from_american`]. Words with the bath and palm vowel, like "path" and "father", are phonemized on
+/// their own and get `ɑː`.
+pub struct BritishPhonemizer<'a, P: Phonemizer + ?Sized> {
+    american: &'a P,
+}
+
+impl<'a, P: Phonemizer + ?Sized> BritishPhonemizer<'a, P> {
+    pub fn new(american: &'a P) -> Self {
+        BritishPhonemizer { american }
+    }
+}
+
+impl<P: Phonemizer + ?Sized> Phonemizer for BritishPhonemizer<'_, P> {
+    fn phonemize(&self, text: &str) -> Result<String> {
+        let phonemize = |text: &str| Ok(british::from_american(&self.american.phonemize(text)?));
+
+        // Broad a words are turned into pronunciation overrides
+        let mut marked = String::with_capacity(text.len());
+        let mut last = 0;
+        for word in BROAD_A_REGEX.find_iter(text) {
+            let phonemes = self.american.phonemize(word.as_str())?;
+            let phonemes = match phonemes.find(['æ', 'ɑ']) {
+                Some(i) => {
+                    let vowel = phonemes[i..].chars().next().unwrap();
+                    format!("{}ɑː{}", &phonemes[..i], &phonemes[i + vowel.len_utf8()..])
+                }
+                None => phonemes,
+            };
+
+            marked.push_str(&text[last..word.start()]);
+            marked.push_str(&format!(
+                "[{}](/{}/)",
+                word.as_str(),
+                british::from_american(&phonemes).trim()
+            ));
+            last = word.end();
+        }
+        marked.push_str(&text[last..]);
+
+        overrides::phonemize(&marked, &phonemize, Language::EnGb, false)
+    }
+}
+
+/// Phonemizers by language, so that text can be phonemized in the language of the voice. External phonemizers
+/// for languages without a built-in one, like espeak-ng for Spanish, are plugged in here.
+///
+/// British English falls back to the phonemizer of American English, wrapped in a [`BritishPhonemizer`].
+#[derive(Default)]
+pub struct Phonemizers {
+    phonemizers: HashMap<Language, Box<dyn Phonemizer>>,
+}
+
+impl Phonemizers {
+    pub fn new() -> Self {
+        Phonemizers::default()
+    }
+
+    /// Set the phonemizer of a language, replacing the previous one.
+    pub fn insert(&mut self, language: Language, phonemizer: impl Phonemizer + 'static) {
+        self.phonemizers.insert(language, Box::new(phonemizer));
+    }
+
+    /// Phonemizer set for a language, without fallbacks.
+    pub fn get(&self, language: Language) -> Option<&dyn Phonemizer> {
+        self.phonemizers.get(&language).map(Box::as_ref)
+    }
+
+    /// Convert text in the given language into phonemes.
+    ///
+    /// # Errors
+    /// Fails if there is no phonemizer for the language.
+    pub fn phonemize(&self, language: Language, text: &str) -> Result<String> {
+        match (self.get(language), self.get(Language::EnUs)) {
+            (Some(phonemizer), _) => phonemizer.phonemize(text),
+            (None, Some(american)) if language == Language::EnGb => {
+                BritishPhonemizer::new(american).phonemize(text)
+            }
+            _ => bail!("No phonemizer for the language '{language}', pass phonemes instead"),
+        }
+    }
+
+    /// Phonemizer for a language, e.g. to pass to [`Kokoro::text2audio_with`](crate::Kokoro::text2audio_with).
+    pub fn for_language(&self, language: Language) -> impl Phonemizer + '_ {
+        move |text: &str| self.phonemize(language, text)
+    }
+}
+
+#[cfg(feature = "phonemoro")]
+impl Phonemizer for phonemoro::en::phonemizer::EnPhonemizer {
+    fn phonemize(&self, text: &str) -> Result<String> {
+        phonemoro::en::phonemizer::EnPhonemizer::phonemize(self, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closure_phonemizer_test() -> Result<()> {
+        let phonemizer = |text: &str| Ok(text.to_uppercase());
+        let dynamic: &dyn Phonemizer = &phonemizer;
+
+        assert_eq!(dynamic.phonemize("abc")?, "ABC");
+
+        Ok(())
+    }
+
+    #[test]
+    fn phonemizers_test() -> Result<()> {
+        let mut phonemizers = Phonemizers::new();
+        phonemizers.insert(Language::EnUs, |_: &str| Ok("hˈOm".to_owned()));
+        phonemizers.insert(Language::Es, |_: &str| Ok("kˈasa".to_owned()));
+
+        assert_eq!(phonemizers.phonemize(Language::EnUs, "home")?, "hˈOm");
+        assert_eq!(phonemizers.phonemize(Language::EnGb, "home")?, "hˈQm");
+        assert_eq!(phonemizers.for_language(Language::Es).phonemize("casa")?, "kˈasa");
+        assert!(phonemizers.phonemize(Language::Ja, "家").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn british_phonemizer_test() -> Result<()> {
+        let american = |text: &str| {
+            Ok(text
+                .split_whitespace()
+                .map(|word| match word.trim_end_matches('.') {
+                    "The" => "ðə",
+                    "path" => "pˈæθ",
+                    "over" => "ˈOvɚ",
+                    "water" => "wˈɔɾɚ",
+                    _ => "?",
+                })
+                .collect::<Vec<_>>()
+                .join(" "))
+        };
+        let british = BritishPhonemizer::new(&american);
+
+        assert_eq!(
+            british.phonemize("The path over water.")?,
+            "ðə pˈɑːθ ˈQvə wˈɔtə"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,641 @@
+//! A subset of [SSML](https://www.w3.org/TR/speech-synthesis11/), the markup for controlling speech synthesis.
+//!
+//! Supported are `<speak>`, `<p>`, `<s>`, `<break>` with `time` or `strength`, `<prosody rate>`,
+//! `<voice name>`, `<say-as>` interpreted as `characters`, `cardinal` or `date`, `<phoneme>` in IPA or X-SAMPA,
+//! `<sub alias>` and `<emphasis level>`:
+//! ```xml
+//! <speak>
+//!   Call <say-as interpret-as="characters">SOS</say-as> on <say-as interpret-as="date" format="mdy">5/4</say-as>.
+//!   <break time="500ms"/>
+//!   <voice name="bf_emma"><prosody rate="slow">Say <phoneme alphabet="ipa" ph="təmˈɑːtəʊ">tomato</phoneme>.</prosody></voice>
+//! </speak>
+//! ```
+//! Other elements and attributes are reported as warnings, and the text within them is read as it is.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use quick_xml::{
+    Reader,
+    events::{BytesStart, Event},
+};
+
+use crate::{
+    Language, VoiceBlend,
+    phonemes::{Emphasis, xsampa},
+    text::{
+        normalize::{cardinal, date, normalize},
+        overrides::{self, Span},
+        spell::spell,
+    },
+};
+
+/// Part of an SSML document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// Text to be spoken, which can contain [pronunciation overrides](crate::text::overrides) and paragraphs
+    /// separated by blank lines. `voice` is `None` for the default voice, `rate` scales the default speed.
+    Speech {
+        text: String,
+        voice: Option<VoiceBlend>,
+        rate: f32,
+    },
+    /// Silence in seconds.
+    Break(f32),
+}
+
+/// A parsed SSML document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ssml {
+    pub segments: Vec<Segment>,
+    /// Unsupported elements and attributes, and invalid values, which were skipped.
+    pub warnings: Vec<String>,
+}
+
+/// Element that is open while parsing, with what is needed to close it.
+enum Frame {
+    /// Voice before the element changed it.
+    Voice(Option<VoiceBlend>),
+    /// Rate before the element changed it.
+    Rate(f32),
+    Sentence,
+    SayAs {
+        interpret_as: String,
+        format: Option<String>,
+        content: String,
+    },
+    Phoneme {
+        alphabet: String,
+        ph: String,
+        content: String,
+    },
+    Sub {
+        alias: String,
+        content: String,
+    },
+    /// Emphasis, or `None` for the level `none`.
+    Emphasis {
+        emphasis: Option<Emphasis>,
+        content: String,
+    },
+    Other,
+}
+
+impl Frame {
+    /// Buffer collecting the text of elements that replace their content.
+    fn content(&mut self) -> Option<&mut String> {
+        match self {
+            Frame::SayAs { content, .. }
+            | Frame::Phoneme { content, .. }
+            | Frame::Sub { content, .. }
+            | Frame::Emphasis { content, .. } => Some(content),
+            _ => None,
+        }
+    }
+}
+
+struct Parser {
+    language: Language,
+    frames: Vec<Frame>,
+    voice: Option<VoiceBlend>,
+    rate: f32,
+    text: String,
+    ssml: Ssml,
+}
+
+impl Ssml {
+    /// Parse an SSML document. Dates and numbers are read in the given language, unless the voice is changed to
+    /// one of another language.
+    ///
+    /// # Errors
+    /// Fails on malformed XML. Unsupported or invalid markup doesn't fail, but is reported in `warnings`.
+    pub fn parse(ssml: &str, language: Language) -> Result<Self> {
+        let mut parser = Parser {
+            language,
+            frames: Vec::new(),
+            voice: None,
+            rate: 1.0,
+            text: String::new(),
+            ssml: Ssml {
+                segments: Vec::new(),
+                warnings: Vec::new(),
+            },
+        };
+
+        let mut reader = Reader::from_str(ssml);
+        loop {
+            match reader.read_event().context("Parsing SSML failed")? {
+                Event::Start(e) => parser.start(&e)?,
+                Event::Empty(e) => {
+                    parser.start(&e)?;
+                    parser.end();
+                }
+                Event::End(_) => parser.end(),
+                Event::Text(e) => parser.push_text(&collapse_whitespace(&e.unescape()?)),
+                Event::CData(e) => parser.push_text(&collapse_whitespace(std::str::from_utf8(&e)?)),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        parser.flush();
+
+        Ok(parser.ssml)
+    }
+}
+
+impl Parser {
+    fn start(&mut self, element: &BytesStart) -> Result<()> {
+        let name = element.local_name();
+        let name = std::str::from_utf8(name.as_ref())?;
+
+        let frame = match name {
+            "speak" => Frame::Other,
+            "p" | "paragraph" => {
+                self.push_text("\n\n");
+                Frame::Other
+            }
+            "s" | "sentence" => {
+                self.push_text(" ");
+                Frame::Sentence
+            }
+            "break" => {
+                let seconds = self.break_seconds(element)?;
+                self.flush();
+                self.ssml.segments.push(Segment::Break(seconds));
+                Frame::Other
+            }
+            "prosody" => {
+                for unsupported in ["pitch", "volume", "contour", "range", "duration"] {
+                    if attribute(element, unsupported)?.is_some() {
+                        self.warn(format!(
+                            "Unsupported attribute '{unsupported}' of <prosody>"
+                        ));
+                    }
+                }
+                let rate = match attribute(element, "rate")? {
+                    Some(rate) => parse_rate(&rate).or_else(|| {
+                        self.warn(format!("Invalid rate '{rate}', keeping the current rate"));
+                        None
+                    }),
+                    None => None,
+                };
+
+                self.flush();
+                let frame = Frame::Rate(self.rate);
+                if let Some(rate) = rate {
+                    self.rate *= rate;
+                }
+                frame
+            }
+            "voice" => {
+                let voice = match attribute(element, "name")? {
+                    Some(name) => VoiceBlend::from_str(&name).map_or_else(
+                        |_| {
+                            self.warn(format!("Unknown voice '{name}', keeping the current voice"));
+                            None
+                        },
+                        Some,
+                    ),
+                    None => {
+                        self.warn("Voices can only be selected by name".to_owned());
+                        None
+                    }
+                };
+
+                self.flush();
+                let frame = Frame::Voice(self.voice.clone());
+                if voice.is_some() {
+                    self.voice = voice;
+                }
+                frame
+            }
+            "say-as" => Frame::SayAs {
+                interpret_as: attribute(element, "interpret-as")?.unwrap_or_default(),
+                format: attribute(element, "format")?,
+                content: String::new(),
+            },
+            "phoneme" => Frame::Phoneme {
+                alphabet: attribute(element, "alphabet")?.unwrap_or_else(|| "ipa".to_owned()),
+                ph: attribute(element, "ph")?.unwrap_or_default(),
+                content: String::new(),
+            },
+            "sub" => match attribute(element, "alias")? {
+                Some(alias) => Frame::Sub {
+                    alias,
+                    content: String::new(),
+                },
+                None => {
+                    self.warn("<sub> without alias, reading its content".to_owned());
+                    Frame::Other
+                }
+            },
+            "emphasis" => {
+                let emphasis = match attribute(element, "level")?.as_deref() {
+                    Some("strong") => Some(Emphasis::Strong),
+                    Some("moderate") | None => Some(Emphasis::Moderate),
+                    Some("reduced") => Some(Emphasis::Reduced),
+                    Some("none") => None,
+                    Some(level) => {
+                        self.warn(format!("Invalid emphasis level '{level}'"));
+                        Some(Emphasis::Moderate)
+                    }
+                };
+                Frame::Emphasis {
+                    emphasis,
+                    content: String::new(),
+                }
+            }
+            _ => {
+                self.warn(format!("Unsupported element <{name}>, reading its content"));
+                Frame::Other
+            }
+        };
+        self.frames.push(frame);
+
+        Ok(())
+    }
+
+    fn end(&mut self) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+
+        match frame {
+            Frame::Voice(voice) => {
+                self.flush();
+                self.voice = voice;
+            }
+            Frame::Rate(rate) => {
+                self.flush();
+                self.rate = rate;
+            }
+            Frame::Sentence => {
+                let text = self.buffer();
+                let len = text.trim_end().len();
+                text.truncate(len);
+                if !text.ends_with(['.', '!', '?', '…']) {
+                    text.push('.');
+                }
+                text.push(' ');
+            }
+            Frame::SayAs {
+                interpret_as,
+                format,
+                content,
+            } => {
+                let text = self.say_as(&interpret_as, format.as_deref(), content.trim());
+                self.push_text(&text);
+            }
+            Frame::Phoneme {
+                alphabet,
+                ph,
+                content,
+            } => {
+                let text = self.phoneme(&alphabet, &ph, content.trim());
+                self.push_text(&text);
+            }
+            Frame::Sub { alias, .. } => self.push_text(&alias),
+            Frame::Emphasis { emphasis, content } => {
+                let text = content.trim();
+                // Markup can't be nested, so content with markup like a <phoneme> is read without emphasis
+                let text = match emphasis {
+                    Some(emphasis) if !text.is_empty() && !text.contains(['[', ']']) => {
+                        Span::Emphasis { text, emphasis }.to_string()
+                    }
+                    _ => text.to_owned(),
+                };
+                self.push_text(&text);
+            }
+            Frame::Other => {}
+        }
+    }
+
+    /// Text read for `<say-as>`, or the content itself if it can't be interpreted.
+    fn say_as(&mut self, interpret_as: &str, format: Option<&str>, content: &str) -> String {
+        let language = self.language();
+        if !language.is_english() {
+            self.warn(format!(
+                "<say-as> is only supported for English, reading '{content}' as it is"
+            ));
+            return content.to_owned();
+        }
+
+        let text = match interpret_as {
+            "characters" | "spell-out" => Some(spell(content, language)),
+            "cardinal" | "number" => Some(match content.replace(',', "").parse::<u64>() {
+                Ok(number) => cardinal(number, language),
+                Err(_) => normalize(content, language),
+            }),
+            "date" => {
+                let date = say_date(content, format, language);
+                if date.is_none() {
+                    self.warn(format!("Invalid date '{content}', reading it as it is"));
+                }
+                date
+            }
+            _ => {
+                self.warn(format!(
+                    "Unsupported interpretation '{interpret_as}' of <say-as>, reading its content"
+                ));
+                None
+            }
+        };
+
+        text.unwrap_or_else(|| content.to_owned())
+    }
+
+    /// Content of `<phoneme>` as a pronunciation override, or the content itself if the phonemes are invalid.
+    fn phoneme(&mut self, alphabet: &str, ph: &str, content: &str) -> String {
+        let ipa = match alphabet {
+            "ipa" => Ok(ph.to_owned()),
+            "x-sampa" | "xsampa" => xsampa::to_ipa(ph),
+            _ => {
+                self.warn(format!(
+                    "Unsupported phonetic alphabet '{alphabet}', reading '{content}' as it is"
+                ));
+                return content.to_owned();
+            }
+        };
+
+        // Phonemes containing the delimiters of overrides would end up being read as text
+        let markup = ipa.map(|ipa| format!("[{content}](/{}/)", ipa.trim()));
+        match markup {
+            Ok(markup)
+                if overrides::spans(&markup)
+                    .is_ok_and(|spans| matches!(spans[..], [Span::Override { .. }])) =>
+            {
+                markup
+            }
+            _ => {
+                self.warn(format!(
+                    "Invalid phonemes '{ph}' for '{content}', reading it as it is"
+                ));
+                content.to_owned()
+            }
+        }
+    }
+
+    fn break_seconds(&mut self, element: &BytesStart) -> Result<f32> {
+        if let Some(time) = attribute(element, "time")? {
+            match parse_time(&time) {
+                Some(seconds) => return Ok(seconds),
+                None => self.warn(format!("Invalid break time '{time}'")),
+            }
+        }
+
+        Ok(match attribute(element, "strength")?.as_deref() {
+            Some("none") => 0.0,
+            Some("x-weak") => 0.1,
+            Some("weak") => 0.25,
+            Some("medium") | None => 0.5,
+            Some("strong") => 0.75,
+            Some("x-strong") => 1.2,
+            Some(strength) => {
+                self.warn(format!("Invalid break strength '{strength}'"));
+                0.5
+            }
+        })
+    }
+
+    /// Language of the current voice.
+    fn language(&self) -> Language {
+        self.voice
+            .as_ref()
+            .map_or(self.language, VoiceBlend::language)
+    }
+
+    /// Buffer of the innermost element collecting its content, or the text of the current segment.
+    fn buffer(&mut self) -> &mut String {
+        match self.frames.iter_mut().rev().find_map(Frame::content) {
+            Some(content) => content,
+            None => &mut self.text,
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        self.buffer().push_str(text);
+    }
+
+    /// End the current segment, e.g. because the voice changes.
+    fn flush(&mut self) {
+        let paragraphs: Vec<String> = self
+            .text
+            .split("\n\n")
+            .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect();
+        self.text.clear();
+
+        if !paragraphs.is_empty() {
+            self.ssml.segments.push(Segment::Speech {
+                text: paragraphs.join("\n\n"),
+                voice: self.voice.clone(),
+                rate: self.rate,
+            });
+        }
+    }
+
+    fn warn(&mut self, warning: String) {
+        if !self.ssml.warnings.contains(&warning) {
+            self.ssml.warnings.push(warning);
+        }
+    }
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>> {
+    Ok(match element.try_get_attribute(name)? {
+        Some(attribute) => Some(attribute.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+/// Whitespace collapsed into single spaces, including at the start and end.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c.is_whitespace() {
+            true if collapsed.ends_with(' ') => {}
+            true => collapsed.push(' '),
+            false => collapsed.push(c),
+        }
+    }
+
+    collapsed
+}
+
+/// Duration like `500ms` or `1.5s` in seconds.
+fn parse_time(time: &str) -> Option<f32> {
+    let time = time.trim();
+    let (value, scale) = match time.strip_suffix("ms") {
+        Some(ms) => (ms, 0.001),
+        None => (time.strip_suffix('s')?, 1.0),
+    };
+    let seconds = value.trim().parse::<f32>().ok()? * scale;
+
+    (seconds.is_finite() && seconds >= 0.0).then_some(seconds)
+}
+
+/// Rate like `slow`, `80%`, `+10%` or `1.2` as a factor of the current rate.
+fn parse_rate(rate: &str) -> Option<f32> {
+    let rate = rate.trim();
+    let factor = match rate {
+        "x-slow" => 0.5,
+        "slow" => 0.75,
+        "medium" | "default" => 1.0,
+        "fast" => 1.25,
+        "x-fast" => 1.5,
+        _ => match rate.strip_suffix('%') {
+            Some(change) if change.starts_with(['+', '-']) => {
+                1.0 + change.parse::<f32>().ok()? / 100.0
+            }
+            Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+            None => rate.parse().ok()?,
+        },
+    };
+
+    (factor.is_finite() && factor > 0.0).then_some(factor)
+}
+
+/// Spoken date of digits in the order of `format`, like `mdy` or `ymd`. Without a format, dates starting with a
+/// four digit year are read as `ymd`, others in the order common for the language.
+fn say_date(text: &str, format: Option<&str>, language: Language) -> Option<String> {
+    let parts: Vec<&str> = text
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    let format = match format {
+        Some(format) => format,
+        None if parts.len() == 3 && parts[0].len() == 4 => "ymd",
+        None => match language {
+            Language::EnGb => &"dmy"[..parts.len().min(3)],
+            _ => &"mdy"[..parts.len().min(3)],
+        },
+    };
+    if format.len() != parts.len() {
+        return None;
+    }
+
+    let (mut day, mut month, mut year) = (None, None, None);
+    for (field, part) in format.chars().zip(parts) {
+        match field {
+            'd' => day = Some(part),
+            'm' => month = Some(part),
+            'y' => year = Some(part),
+            _ => return None,
+        }
+    }
+
+    date(day?, month?, year, language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KokoroVoice;
+
+    fn speech(text: &str, voice: Option<VoiceBlend>, rate: f32) -> Segment {
+        Segment::Speech {
+            text: text.to_owned(),
+            voice,
+            rate,
+        }
+    }
+
+    #[test]
+    fn parse_test() -> Result<()> {
+        let ssml = Ssml::parse(
+            r#"<speak>
+              <p>Hello <sub alias="World Wide Web">WWW</sub>!</p>
+              <break time="500ms"/>
+              <voice name="bf_emma">
+                <prosody rate="slow">Slowly <prosody rate="+100%">and fast</prosody></prosody>
+              </voice>
+              <break strength="strong"/>
+              <s>Last sentence</s>
+            </speak>"#,
+            Language::EnUs,
+        )?;
+
+        let emma = Some(VoiceBlend::from(KokoroVoice::BF_EMMA));
+        assert_eq!(
+            ssml.segments,
+            vec![
+                speech("Hello World Wide Web!", None, 1.0),
+                Segment::Break(0.5),
+                speech("Slowly", emma.clone(), 0.75),
+                speech("and fast", emma, 1.5),
+                Segment::Break(0.75),
+                speech("Last sentence.", None, 1.0),
+            ]
+        );
+        assert!(ssml.warnings.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn say_as_and_phoneme_test() -> Result<()> {
+        let ssml = Ssml::parse(
+            r#"<speak>
+              <say-as interpret-as="characters">AB1</say-as>,
+              <say-as interpret-as="cardinal">1,024</say-as>,
+              <say-as interpret-as="date" format="dmy">1/5/2020</say-as>,
+              <phoneme alphabet="ipa" ph="təmˈɑːtəʊ">tomato</phoneme>,
+              <phoneme alphabet="x-sampa" ph="t@&quot;meIt@U">tomato</phoneme>,
+              <emphasis level="strong">really</emphasis> <emphasis level="none">not</emphasis>
+            </speak>"#,
+            Language::EnUs,
+        )?;
+
+        assert_eq!(
+            ssml.segments,
+            vec![speech(
+                "[A](/ˈeɪ/), [B](/bˈi/), one, one thousand twenty-four, May first, twenty twenty, \
+                 [tomato](/təmˈɑːtəʊ/), [tomato](/təˈmeɪtəʊ/), [really](++) not",
+                None,
+                1.0
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn warnings_test() -> Result<()> {
+        let ssml = Ssml::parse(
+            r#"<speak><audio src="very.wav">Very</audio> <voice name="nobody">well</voice>
+              <phoneme ph="w3d">word</phoneme> <prosody pitch="high" rate="fastest">now</prosody>
+              <say-as interpret-as="date">13/13</say-as></speak>"#,
+            Language::EnGb,
+        )?;
+
+        assert_eq!(
+            ssml.segments,
+            vec![
+                speech("Very", None, 1.0),
+                speech("well", None, 1.0),
+                speech("word", None, 1.0),
+                speech("now", None, 1.0),
+                speech("13/13", None, 1.0),
+            ]
+        );
+        assert_eq!(ssml.warnings.len(), 6);
+
+        assert!(Ssml::parse("<speak><p>Unclosed</speak>", Language::EnUs).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rate_and_time_test() {
+        assert_eq!(parse_rate("x-fast"), Some(1.5));
+        assert_eq!(parse_rate("80%"), Some(0.8));
+        assert_eq!(parse_rate("-20%"), Some(0.8));
+        assert_eq!(parse_rate("1.2"), Some(1.2));
+        assert_eq!(parse_rate("-150%"), None);
+        assert_eq!(parse_time("1.5s"), Some(1.5));
+        assert_eq!(parse_time("250ms"), Some(0.25));
+        assert_eq!(parse_time("3"), None);
+    }
+}
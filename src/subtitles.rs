@@ -0,0 +1,224 @@
+//! Subtitles in the SRT and WebVTT formats.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, ensure};
+use strum::{EnumString, VariantNames};
+
+/// A single subtitle, shown from `start` to `end`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+/// Supported subtitle formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    /// File extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+
+    /// Render cues in this format.
+    pub fn render(&self, cues: &[Cue]) -> String {
+        match self {
+            SubtitleFormat::Srt => to_srt(cues),
+            SubtitleFormat::Vtt => to_vtt(cues),
+        }
+    }
+}
+
+/// Render cues as SRT.
+pub fn to_srt(cues: &[Cue]) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                timestamp(cue.start, ','),
+                timestamp(cue.end, ','),
+                cue.text
+            )
+        })
+        .collect()
+}
+
+/// Render cues as WebVTT.
+pub fn to_vtt(cues: &[Cue]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for cue in cues {
+        vtt += &format!(
+            "{} --> {}\n{}\n\n",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.'),
+            // "-->" isn't allowed within cue text
+            cue.text.replace("-->", "->")
+        );
+    }
+    vtt
+}
+
+/// Parse cues from SRT. The lines of a cue's text are joined by newlines, formatting tags are kept as is.
+pub fn parse_srt(srt: &str) -> Result<Vec<Cue>> {
+    let srt = srt.trim_start_matches('\u{feff}');
+
+    let mut cues = Vec::new();
+    let mut block: Vec<(usize, &str)> = Vec::new();
+    for (i, line) in srt.lines().enumerate().chain([(usize::MAX, "")]) {
+        if !line.trim().is_empty() {
+            block.push((i + 1, line));
+            continue;
+        }
+        if block.is_empty() {
+            continue;
+        }
+
+        // The index line is optional, the timing line is identified by its arrow
+        let timing = block
+            .iter()
+            .position(|(_, line)| line.contains("-->"))
+            .ok_or_else(|| anyhow!("Cue starting in line {} has no timing", block[0].0))?;
+        let (line_num, timing_line) = block[timing];
+
+        let (start, end) = timing_line.split_once("-->").unwrap();
+        // Coordinates may follow the end time
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let start = parse_timestamp(start.trim())
+            .with_context(|| format!("Invalid start time in line {line_num}"))?;
+        let end =
+            parse_timestamp(end).with_context(|| format!("Invalid end time in line {line_num}"))?;
+        ensure!(end >= start, "Cue in line {line_num} ends before it starts");
+
+        cues.push(Cue {
+            start,
+            end,
+            text: block[timing + 1..]
+                .iter()
+                .map(|(_, line)| line.trim())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        });
+        block.clear();
+    }
+
+    Ok(cues)
+}
+
+/// Parse `[HH:]MM:SS,mmm`, also accepting `.` as the separator of the milliseconds.
+fn parse_timestamp(timestamp: &str) -> Result<Duration> {
+    let (clock, millis) = timestamp
+        .split_once([',', '.'])
+        .ok_or_else(|| anyhow!("Missing milliseconds in timestamp '{timestamp}'"))?;
+
+    let mut seconds = 0u64;
+    let parts: Vec<&str> = clock.split(':').collect();
+    ensure!(
+        (2..=3).contains(&parts.len()),
+        "Invalid timestamp '{timestamp}'"
+    );
+    for part in parts {
+        seconds = seconds * 60 + part.trim().parse::<u64>()?;
+    }
+
+    // Fewer than 3 digits are fractions, e.g. "5" means 500ms
+    ensure!(
+        !millis.is_empty() && millis.len() <= 3,
+        "Invalid milliseconds in timestamp '{timestamp}'"
+    );
+    let millis = millis.parse::<u64>()? * 10u64.pow(3 - millis.len() as u32);
+
+    Ok(Duration::from_secs(seconds) + Duration::from_millis(millis))
+}
+
+/// Format as `HH:MM:SS` followed by the separator and milliseconds.
+fn timestamp(time: Duration, separator: char) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cues() -> Vec<Cue> {
+        vec![
+            Cue {
+                start: Duration::from_millis(0),
+                end: Duration::from_millis(1_500),
+                text: "Hello there.".to_owned(),
+            },
+            Cue {
+                start: Duration::from_millis(1_700),
+                end: Duration::from_millis(3_723_004),
+                text: "How are you?".to_owned(),
+            },
+        ]
+    }
+
+    #[test]
+    fn srt_test() {
+        let expected = "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n\
+                        2\n00:00:01,700 --> 01:02:03,004\nHow are you?\n\n";
+
+        assert_eq!(to_srt(&cues()), expected);
+    }
+
+    #[test]
+    fn vtt_test() {
+        let expected = "WEBVTT\n\n\
+                        00:00:00.000 --> 00:00:01.500\nHello there.\n\n\
+                        00:00:01.700 --> 01:02:03.004\nHow are you?\n\n";
+
+        assert_eq!(to_vtt(&cues()), expected);
+    }
+
+    #[test]
+    fn parse_srt_roundtrip_test() {
+        assert_eq!(parse_srt(&to_srt(&cues())).unwrap(), cues());
+    }
+
+    #[test]
+    fn parse_srt_test() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,5 X1:10 X2:20\r\n<i>Two</i>\r\nlines\r\n\r\n\r\n\
+                   00:03.250 --> 00:04.000\nNo index\n";
+        let expected = vec![
+            Cue {
+                start: Duration::from_millis(1_000),
+                end: Duration::from_millis(2_500),
+                text: "<i>Two</i>\nlines".to_owned(),
+            },
+            Cue {
+                start: Duration::from_millis(3_250),
+                end: Duration::from_millis(4_000),
+                text: "No index".to_owned(),
+            },
+        ];
+
+        assert_eq!(parse_srt(srt).unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_invalid_srt_test() {
+        assert!(parse_srt("1\nHello").is_err());
+        assert!(parse_srt("00:00:02,000 --> 00:00:01,000\nBackwards").is_err());
+        assert!(parse_srt("00:00:01 --> 00:00:02,000\nNo millis").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Pronunciation lexicons, which give the phonemes of words the phonemizer gets wrong, like brand names.
+//!
+//! Lexicons are written in TOML, where words map to their phonemes or to entries with options:
+//! ```toml
+//! Kokoro = "kˈOkəɹO"
+//! nginx = { phonemes = "ˈɛnʤɪnˈɛks", case_sensitive = true }
+//! read = [{ phonemes = "ɹˈid", pos = "VB" }, { phonemes = "ɹˈɛd", pos = "VBD" }]
+//! ```
+//! Or as tab separated values, with one entry per line and the columns word, phonemes and optionally part of
+//! speech and `true` for case sensitivity. Lines starting with `#` are comments.
+
+use std::path::Path;
+
+use anyhow::{Context, Result, bail, ensure};
+use regex::Regex;
+use serde::Deserialize;
+
+use super::overrides::{self, Span};
+use crate::{kokoro::tokenizer::KokoroTokenizer, phonemes};
+
+/// Pronunciation of a word or phrase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub word: String,
+    pub phonemes: String,
+    /// Whether the word only matches with the same case, e.g. to tell "US" from "us".
+    pub case_sensitive: bool,
+    /// Part of speech the pronunciation is used for, like `VBD` for "read" in the past tense.
+    pub pos: Option<String>,
+}
+
+impl Entry {
+    /// Symbols of the phonemes that Kokoro doesn't know, after mapping them with [`phonemes::to_kokoro`].
+    pub fn unknown_symbols(&self) -> Vec<char> {
+        let mut unknown = Vec::new();
+        for c in phonemes::to_kokoro(&self.phonemes).chars() {
+            if !c.is_whitespace() && !KokoroTokenizer::is_known(c) && !unknown.contains(&c) {
+                unknown.push(c);
+            }
+        }
+
+        unknown
+    }
+
+    fn matches(&self, word: &str) -> bool {
+        match self.case_sensitive {
+            true => self.word == word,
+            false => self.word.to_lowercase() == word.to_lowercase(),
+        }
+    }
+}
+
+/// Entries of a TOML lexicon.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TomlEntries {
+    Phonemes(String),
+    Entry(TomlEntry),
+    Entries(Vec<TomlEntry>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlEntry {
+    phonemes: String,
+    #[serde(default)]
+    case_sensitive: bool,
+    pos: Option<String>,
+}
+
+/// Collection of pronunciations that are applied to text before it is phonemized.
+#[derive(Debug, Clone)]
+pub struct Lexicon {
+    entries: Vec<Entry>,
+    /// Matches the words of all entries, longest first.
+    regex: Option<Regex>,
+}
+
+impl Lexicon {
+    /// Create a lexicon. Earlier entries take precedence over later ones for the same word.
+    pub fn new(entries: Vec<Entry>) -> Result<Self> {
+        for entry in &entries {
+            ensure!(
+                !entry.word.trim().is_empty() && !entry.word.contains(['[', ']']),
+                "Invalid word '{}' in lexicon",
+                entry.word
+            );
+            ensure!(
+                !entry.phonemes.trim().is_empty() && !entry.phonemes.contains(['/', '(', ')']),
+                "Invalid phonemes '{}' for '{}' in lexicon",
+                entry.phonemes,
+                entry.word
+            );
+        }
+
+        // Words are only matched as a whole, unless they start or end with a symbol like "C++"
+        let boundary = |c: Option<char>| match c.is_some_and(char::is_alphanumeric) {
+            true => r"\b",
+            false => "",
+        };
+
+        let mut words: Vec<&Entry> = entries.iter().collect();
+        words.sort_by_key(|entry| std::cmp::Reverse(entry.word.chars().count()));
+        let patterns: Vec<String> = words
+            .iter()
+            .map(|entry| {
+                let word = entry.word.trim();
+                let flags = if entry.case_sensitive { "" } else { "i" };
+                format!(
+                    "{}(?{flags}:{}){}",
+                    boundary(word.chars().next()),
+                    regex::escape(word),
+                    boundary(word.chars().last())
+                )
+            })
+            .collect();
+
+        let regex = match patterns.is_empty() {
+            true => None,
+            false => Some(Regex::new(&patterns.join("|")).context("Building lexicon failed")?),
+        };
+
+        Ok(Lexicon { entries, regex })
+    }
+
+    /// Read a lexicon from a file, which is parsed as TOML if it has the extension `toml`, and as tab separated
+    /// values otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading lexicon from {path:?} failed"))?;
+
+        match path.extension().is_some_and(|ext| ext == "toml") {
+            true => Lexicon::from_toml(&content),
+            false => Lexicon::from_tsv(&content),
+        }
+        .with_context(|| format!("Parsing lexicon {path:?} failed"))
+    }
+
+    /// Parse a lexicon in TOML. Entries keep the order of the file.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let table: toml::Table = toml::from_str(toml)?;
+
+        let mut entries = Vec::new();
+        for (word, value) in table {
+            let toml_entries = match value
+                .try_into()
+                .with_context(|| format!("Invalid entry for '{word}'"))?
+            {
+                TomlEntries::Phonemes(phonemes) => vec![TomlEntry {
+                    phonemes,
+                    case_sensitive: false,
+                    pos: None,
+                }],
+                TomlEntries::Entry(entry) => vec![entry],
+                TomlEntries::Entries(entries) => entries,
+            };
+
+            entries.extend(toml_entries.into_iter().map(|entry| Entry {
+                word: word.clone(),
+                phonemes: entry.phonemes,
+                case_sensitive: entry.case_sensitive,
+                pos: entry.pos,
+            }));
+        }
+
+        Lexicon::new(entries)
+    }
+
+    /// Parse a lexicon in tab separated values. Empty lines and lines starting with `#` are skipped.
+    pub fn from_tsv(tsv: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (i, line) in tsv.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let (word, phonemes, pos, case_sensitive) = match columns[..] {
+                [word, phonemes] => (word, phonemes, "", ""),
+                [word, phonemes, pos] => (word, phonemes, pos, ""),
+                [word, phonemes, pos, case_sensitive] => (word, phonemes, pos, case_sensitive),
+                _ => bail!("Line {} needs 2 to 4 tab separated columns", i + 1),
+            };
+
+            entries.push(Entry {
+                word: word.to_owned(),
+                phonemes: phonemes.to_owned(),
+                case_sensitive: match case_sensitive {
+                    "" => false,
+                    value => value
+                        .parse()
+                        .with_context(|| format!("Line {}: invalid case sensitivity", i + 1))?,
+                },
+                pos: (!pos.is_empty()).then(|| pos.to_owned()),
+            });
+        }
+
+        Lexicon::new(entries)
+    }
+
+    /// All entries, in the order of precedence.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Find the pronunciation of a word. Entries for the given part of speech are preferred over entries without
+    /// one.
+    pub fn lookup(&self, word: &str, pos: Option<&str>) -> Option<&Entry> {
+        let entries = || self.entries.iter().filter(|entry| entry.matches(word));
+
+        pos.and_then(|pos| entries().find(|entry| entry.pos.as_deref() == Some(pos)))
+            .or_else(|| entries().find(|entry| entry.pos.is_none()))
+    }
+
+    /// Give words of the text that are in the lexicon their pronunciation as inline overrides, which are then
+    /// used instead of the phonemizer, see [`overrides`]. Existing markup is kept. Since the part of speech
+    /// of words isn't known, entries without one are preferred, otherwise the first entry of a word is applied.
+    pub fn apply(&self, text: &str) -> Result<String> {
+        let Some(regex) = &self.regex else {
+            return Ok(text.to_owned());
+        };
+
+        let mut result = String::with_capacity(text.len());
+        for span in overrides::spans(text)? {
+            match span {
+                Span::Text(text) => {
+                    let replaced = regex.replace_all(text, |captures: &regex::Captures| {
+                        let word = &captures[0];
+                        let entry = self
+                            .lookup(word, None)
+                            .or_else(|| self.entries.iter().find(|entry| entry.matches(word)));
+                        match entry {
+                            Some(entry) => format!("[{word}](/{}/)", entry.phonemes.trim()),
+                            None => word.to_owned(),
+                        }
+                    });
+                    result.push_str(&replaced);
+                }
+                span => result.push_str(&span.to_string()),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_toml_test() -> Result<()> {
+        let lexicon = Lexicon::from_toml(
+            r#"
+            Kokoro = "kˈOkəɹO"
+            nginx = { phonemes = "ˈɛnʤɪnˈɛks", case_sensitive = true }
+            read = [{ phonemes = "ɹˈid", pos = "VB" }, { phonemes = "ɹˈɛd", pos = "VBD" }]
+            "#,
+        )?;
+
+        assert_eq!(lexicon.entries().len(), 4);
+        assert_eq!(lexicon.lookup("KOKORO", None).unwrap().phonemes, "kˈOkəɹO");
+        assert!(lexicon.lookup("NGINX", None).is_none());
+        assert_eq!(
+            lexicon.lookup("read", Some("VBD")).unwrap().phonemes,
+            "ɹˈɛd"
+        );
+        assert!(lexicon.lookup("read", None).is_none());
+
+        assert!(Lexicon::from_toml("word = { phonemes = \"wˈɜɹd\", color = 1 }").is_err());
+        assert!(Lexicon::from_toml("word = 1").is_err());
+
+        let lexicon = Lexicon::from_toml("zebra = \"zˈibɹə\"\napple = \"ˈæpəl\"")?;
+        assert_eq!(lexicon.entries()[0].word, "zebra");
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_tsv_test() -> Result<()> {
+        let lexicon = Lexicon::from_tsv(
+            "# word\tphonemes\tpos\tcase sensitive\n\
+             Kokoro\tkˈOkəɹO\n\
+             \n\
+             US\tjˌuˈɛs\t\ttrue\n\
+             read\tɹˈɛd\tVBD\n",
+        )?;
+
+        assert_eq!(
+            lexicon.entries()[1],
+            Entry {
+                word: "US".to_owned(),
+                phonemes: "jˌuˈɛs".to_owned(),
+                case_sensitive: true,
+                pos: None,
+            }
+        );
+        assert_eq!(lexicon.entries()[2].pos.as_deref(), Some("VBD"));
+
+        assert!(Lexicon::from_tsv("word").is_err());
+        assert!(Lexicon::from_tsv("word\twˈɜɹd\t\tmaybe").is_err());
+        assert!(Lexicon::from_tsv("word\tw/ɜɹd").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn apply_test() -> Result<()> {
+        let lexicon = Lexicon::from_tsv(
+            "Kokoro\tkˈOkəɹO\n\
+             US\tjˌuˈɛs\t\ttrue\n\
+             New York\tnu jˈɔɹk\n\
+             C++\tsˌi plʌs plˈʌs\n\
+             read\tɹˈɛd\tVBD\n",
+        )?;
+
+        assert_eq!(
+            lexicon.apply("Kokoro's voice tells us about the US and new york.")?,
+            "[Kokoro](/kˈOkəɹO/)'s voice tells us about the [US](/jˌuˈɛs/) and [new york](/nu jˈɔɹk/)."
+        );
+        assert_eq!(
+            lexicon.apply("I read C++ books, not kokoros or [Kokoro](/kəkˈɔɹoʊ/).")?,
+            "I [read](/ɹˈɛd/) [C++](/sˌi plʌs plˈʌs/) books, not kokoros or [Kokoro](/kəkˈɔɹoʊ/)."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_symbols_test() -> Result<()> {
+        let lexicon = Lexicon::from_tsv("Kokoro\tkˈOkəɹO\nwürde\tvʏrdə\nthree\tθɹ3i3\n")?;
+        let unknown: Vec<Vec<char>> = lexicon
+            .entries()
+            .iter()
+            .map(Entry::unknown_symbols)
+            .collect();
+
+        assert_eq!(unknown, vec![vec![], vec!['ʏ'], vec!['3']]);
+
+        Ok(())
+    }
+}
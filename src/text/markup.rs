@@ -0,0 +1,461 @@
+//! Extraction of readable text from Markdown and HTML.
+
+use std::sync::LazyLock;
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use regex::{Captures, Regex};
+use scraper::{ElementRef, Html, Node};
+use strum::{EnumString, VariantNames};
+
+use super::overrides;
+
+/// Links like https://example.com/page, whose host is read instead of the whole link.
+static URL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\b(?:https?://|www\.)([\w-]+(?:\.[\w-]+)*)(?::\d+)?(?:[/?#](?:[^\s<>()\[\]]*[^\s<>()\[\].,;:!?'"])?)?"#)
+        .expect("Regex for detecting links is not valid.")
+});
+
+/// HTML elements whose text isn't read.
+static HTML_SKIPPED_ELEMENTS: [&str; 9] = [
+    "head", "script", "style", "template", "noscript", "svg", "img", "iframe", "button",
+];
+
+/// HTML elements that separate paragraphs.
+static HTML_BLOCK_ELEMENTS: [&str; 18] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "aside",
+    "header",
+    "footer",
+    "main",
+    "nav",
+    "blockquote",
+    "figure",
+    "figcaption",
+    "details",
+    "summary",
+    "dl",
+    "dt",
+    "dd",
+    "hr",
+];
+
+/// Markup of a text to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum Markup {
+    #[default]
+    Plain,
+    Markdown,
+    Html,
+    /// [SSML](crate::ssml), which is parsed instead of stripped.
+    Ssml,
+}
+
+impl Markup {
+    /// Markup of a file by its extension, if it's one of a markup language.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "md" | "markdown" => Some(Markup::Markdown),
+            "html" | "htm" | "xhtml" => Some(Markup::Html),
+            "ssml" => Some(Markup::Ssml),
+            _ => None,
+        }
+    }
+}
+
+/// How code blocks are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum CodeBlocks {
+    /// Leave code blocks out.
+    Skip,
+    /// Replace code blocks with a short note on their length.
+    #[default]
+    Announce,
+    /// Read code blocks line by line.
+    Read,
+}
+
+/// A block of a document, before it's turned into paragraphs.
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading(String),
+    Paragraph(String),
+    List(Vec<String>),
+    Code(String),
+    Table(Vec<Vec<String>>),
+}
+
+/// Convert Markdown to text for reading. Headings and list items become sentences of their own, links are
+/// read by their text and images are left out. Markup like `[word](/wˈɜɹd/)` and `[word](+)` is kept, see
+/// [`overrides`].
+pub fn markdown_to_text(markdown: &str, code_blocks: CodeBlocks) -> String {
+    render(&markdown_blocks(markdown), code_blocks)
+}
+
+/// Convert HTML to text for reading, like [`markdown_to_text`]. Scripts, styles and images are left out.
+pub fn html_to_text(html: &str, code_blocks: CodeBlocks) -> String {
+    render(&html_blocks(&Html::parse_document(html)), code_blocks)
+}
+
+/// Collects the blocks of a document.
+#[derive(Default)]
+struct Builder {
+    blocks: Vec<Block>,
+    inline: String,
+}
+
+impl Builder {
+    fn take_inline(&mut self) -> String {
+        std::mem::take(&mut self.inline)
+    }
+
+    fn end_paragraph(&mut self) {
+        let text = self.take_inline();
+        if !text.trim().is_empty() {
+            self.blocks.push(Block::Paragraph(text));
+        }
+    }
+}
+
+fn markdown_blocks(markdown: &str) -> Vec<Block> {
+    let mut builder = Builder::default();
+    let mut list: Vec<String> = Vec::new();
+    let mut list_depth = 0;
+    let mut table: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut code: Option<String> = None;
+    let mut html = String::new();
+    let mut image_depth = 0;
+    // Destinations of links that are markup like [word](/wˈɜɹd/) or [word](+), which are kept
+    let mut links: Vec<Option<String>> = Vec::new();
+
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => builder.end_paragraph(),
+            Event::End(TagEnd::Heading(_)) => {
+                let heading = builder.take_inline();
+                builder.blocks.push(Block::Heading(heading));
+            }
+            Event::End(TagEnd::Paragraph) if list_depth > 0 => builder.inline.push('\n'),
+            Event::End(TagEnd::Paragraph) => builder.end_paragraph(),
+            Event::Start(Tag::List(_)) => {
+                // Items of nested lists follow the item they are nested in
+                if list_depth == 0 {
+                    builder.end_paragraph();
+                } else {
+                    list.push(builder.take_inline());
+                }
+                list_depth += 1;
+            }
+            Event::End(TagEnd::Item) => list.push(builder.take_inline()),
+            Event::End(TagEnd::List(_)) => {
+                list_depth -= 1;
+                if list_depth == 0 {
+                    let items = std::mem::take(&mut list)
+                        .into_iter()
+                        .filter(|item| !item.trim().is_empty())
+                        .collect();
+                    builder.blocks.push(Block::List(items));
+                }
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                builder.end_paragraph();
+                code = Some(String::new());
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(code) = code.take() {
+                    builder.blocks.push(Block::Code(code));
+                }
+            }
+            Event::Start(Tag::Table(_)) => builder.end_paragraph(),
+            Event::End(TagEnd::TableCell) => row.push(builder.take_inline()),
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => {
+                table.push(std::mem::take(&mut row))
+            }
+            Event::End(TagEnd::Table) => builder
+                .blocks
+                .push(Block::Table(std::mem::take(&mut table))),
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                let is_markup = overrides::is_markup_destination(&dest_url);
+                if is_markup {
+                    builder.inline.push('[');
+                }
+                links.push(is_markup.then(|| dest_url.into_string()));
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some(Some(destination)) = links.pop() {
+                    builder.inline.push_str(&format!("]({destination})"));
+                }
+            }
+            Event::Start(Tag::Image { .. }) => image_depth += 1,
+            Event::End(TagEnd::Image) => image_depth -= 1,
+            Event::Html(raw) => html.push_str(&raw),
+            Event::End(TagEnd::HtmlBlock) => {
+                builder.end_paragraph();
+                let fragment = Html::parse_fragment(&std::mem::take(&mut html));
+                builder.blocks.extend(html_blocks(&fragment));
+            }
+            Event::Text(text) => match &mut code {
+                Some(code) => code.push_str(&text),
+                None if image_depth > 0 => {}
+                None => builder.inline.push_str(&text),
+            },
+            Event::Code(text) => builder.inline.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => builder.inline.push(' '),
+            _ => {}
+        }
+    }
+    builder.end_paragraph();
+
+    builder.blocks
+}
+
+fn html_blocks(document: &Html) -> Vec<Block> {
+    let mut builder = Builder::default();
+    walk_html(document.root_element(), &mut builder);
+    builder.end_paragraph();
+
+    builder.blocks
+}
+
+fn walk_html(element: ElementRef, builder: &mut Builder) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => builder.inline.push_str(text),
+            Node::Element(_) => {
+                let child = ElementRef::wrap(child).unwrap();
+                match child.value().name() {
+                    name if HTML_SKIPPED_ELEMENTS.contains(&name) => {}
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        builder.end_paragraph();
+                        builder.blocks.push(Block::Heading(inline_text(child)));
+                    }
+                    "ul" | "ol" => {
+                        builder.end_paragraph();
+                        let mut items = Vec::new();
+                        list_items(child, &mut items);
+                        builder.blocks.push(Block::List(items));
+                    }
+                    "pre" => {
+                        builder.end_paragraph();
+                        builder.blocks.push(Block::Code(child.text().collect()));
+                    }
+                    "table" => {
+                        builder.end_paragraph();
+                        let rows = child
+                            .descendants()
+                            .filter_map(ElementRef::wrap)
+                            .filter(|row| row.value().name() == "tr")
+                            .map(|row| {
+                                row.child_elements()
+                                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                                    .map(inline_text)
+                                    .collect()
+                            })
+                            .collect();
+                        builder.blocks.push(Block::Table(rows));
+                    }
+                    "br" => builder.inline.push(' '),
+                    name if HTML_BLOCK_ELEMENTS.contains(&name) => {
+                        builder.end_paragraph();
+                        walk_html(child, builder);
+                        builder.end_paragraph();
+                    }
+                    _ => walk_html(child, builder),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Items of a list, with the items of nested lists following the item they are nested in.
+fn list_items(list: ElementRef, items: &mut Vec<String>) {
+    for item in list
+        .child_elements()
+        .filter(|item| item.value().name() == "li")
+    {
+        let text = inline_text(item);
+        if !text.trim().is_empty() {
+            items.push(text);
+        }
+
+        for nested in item
+            .child_elements()
+            .filter(|nested| matches!(nested.value().name(), "ul" | "ol"))
+        {
+            list_items(nested, items);
+        }
+    }
+}
+
+/// Text of an element, without nested lists and skipped elements.
+fn inline_text(element: ElementRef) -> String {
+    let mut text = String::new();
+    for child in element.children() {
+        match child.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(e) if e.name() == "br" => text.push(' '),
+            Node::Element(e)
+                if HTML_SKIPPED_ELEMENTS.contains(&e.name()) || matches!(e.name(), "ul" | "ol") => {
+            }
+            Node::Element(_) => text.push_str(&inline_text(ElementRef::wrap(child).unwrap())),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Turn blocks into paragraphs separated by blank lines.
+fn render(blocks: &[Block], code_blocks: CodeBlocks) -> String {
+    let paragraphs: Vec<String> = blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Heading(heading) => Some(sentence(heading)),
+            Block::Paragraph(paragraph) => Some(clean(paragraph)),
+            Block::List(items) => Some(lines(items.iter().map(|item| sentence(item)))),
+            Block::Table(rows) => Some(lines(rows.iter().map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| clean(cell))
+                    .filter(|cell| !cell.is_empty())
+                    .collect();
+                sentence(&cells.join(", "))
+            }))),
+            Block::Code(code) => match code_blocks {
+                CodeBlocks::Skip => None,
+                CodeBlocks::Announce => match code.lines().filter(|l| !l.trim().is_empty()).count()
+                {
+                    1 => Some("Code block with 1 line.".to_owned()),
+                    n => Some(format!("Code block with {n} lines.")),
+                },
+                CodeBlocks::Read => Some(lines(code.lines().map(sentence))),
+            },
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+
+    paragraphs.join("\n\n")
+}
+
+/// Join non-empty lines.
+fn lines(lines: impl Iterator<Item = String>) -> String {
+    lines
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Collapse whitespace and replace links by their host.
+fn clean(text: &str) -> String {
+    let text = URL_REGEX.replace_all(text, |caps: &Captures| {
+        caps[1].trim_start_matches("www.").to_owned()
+    });
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Clean text and end it with a period, unless it already ends with punctuation.
+fn sentence(text: &str) -> String {
+    let mut text = clean(text);
+    if !text.is_empty() && !text.ends_with(['.', '!', '?', '…', ':', ';']) {
+        text.push('.');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_to_text_test() {
+        let markdown = "# Getting Started\n\
+                        \n\
+                        Read the [guide](https://example.com/guide) or visit <https://www.example.com/docs/>.\n\
+                        Say [tomato](/təmˈɑːtəʊ/), [not](++) [ABC](spell).\n\
+                        ![Logo](logo.png)\n\
+                        \n\
+                        - Install it\n\
+                        - Run it:\n  \
+                          1. once\n\
+                        - Done!\n\
+                        \n\
+                        ```rust\n\
+                        fn main() {}\n\
+                        ```\n\
+                        \n\
+                        | Name | Value |\n\
+                        |------|-------|\n\
+                        | a    | `1`   |\n\
+                        \n\
+                        <div>Some <b>HTML</b></div>\n";
+
+        let expected = "Getting Started.\n\n\
+                        Read the guide or visit example.com. Say [tomato](/təmˈɑːtəʊ/), [not](++) [ABC](spell).\n\n\
+                        Install it.\nRun it:\nonce.\nDone!\n\n\
+                        Code block with 1 line.\n\n\
+                        Name, Value.\na, 1.\n\n\
+                        Some HTML";
+        assert_eq!(markdown_to_text(markdown, CodeBlocks::Announce), expected);
+
+        let markdown = "Text\n\n    let a = 1;\n    let b = 2;\n";
+        assert_eq!(markdown_to_text(markdown, CodeBlocks::Skip), "Text");
+        assert_eq!(
+            markdown_to_text(markdown, CodeBlocks::Read),
+            "Text\n\nlet a = 1;\nlet b = 2;"
+        );
+    }
+
+    #[test]
+    fn html_to_text_test() {
+        let html = r#"<!DOCTYPE html><html><head><title>Ignored</title><style>p {}</style></head><body>
+            <h2>Intro</h2>
+            <p>See <a href="https://example.com">the docs</a> &amp; http://example.org/a?b=c, please.<br>Thanks
+            <script>if (a < b) {}</script>
+            <ul><li>One<ul><li>Nested</li></ul></li><li>Two</li></ul>
+            <pre><code>x = 1</code></pre>
+            <table><tr><th>Key</th><th>Value</th></tr><tr><td>a</td><td></td></tr></table>
+            </body></html>"#;
+
+        let expected = "Intro.\n\n\
+                        See the docs & example.org, please. Thanks\n\n\
+                        One.\nNested.\nTwo.\n\n\
+                        Code block with 1 line.\n\n\
+                        Key, Value.\na.";
+        assert_eq!(html_to_text(html, CodeBlocks::Announce), expected);
+    }
+
+    #[test]
+    fn markdown_links_test() {
+        let markdown = "See [docs](/docs/), [API](/docs/api/) and [guide](/user_guide/), or [tomato](/təmˈɑːtəʊ/).";
+
+        assert_eq!(
+            markdown_to_text(markdown, CodeBlocks::Announce),
+            "See docs, API and guide, or [tomato](/təmˈɑːtəʊ/)."
+        );
+    }
+
+    #[test]
+    fn from_extension_test() {
+        assert_eq!(Markup::from_extension("MD"), Some(Markup::Markdown));
+        assert_eq!(Markup::from_extension("htm"), Some(Markup::Html));
+        assert_eq!(Markup::from_extension("ssml"), Some(Markup::Ssml));
+        assert_eq!(Markup::from_extension("txt"), None);
+    }
+
+    #[test]
+    fn clean_test() {
+        assert_eq!(
+            clean("Go to https://www.rust-lang.org/learn.  Or www.crates.io!"),
+            "Go to rust-lang.org. Or crates.io!"
+        );
+        assert_eq!(sentence("  A heading "), "A heading.");
+        assert_eq!(sentence("Why?"), "Why?");
+    }
+}
@@ -0,0 +1,69 @@
+//! Preparation of text before it is phonemized.
+
+pub mod lexicon;
+pub mod markup;
+pub mod normalize;
+pub mod overrides;
+pub mod segment;
+pub mod spell;
+
+use std::ops::Range;
+
+pub use segment::{sentence_spans, sentences};
+
+/// Split text into paragraphs, which are separated by at least one blank line. Empty paragraphs are
+/// skipped.
+pub fn paragraphs(text: &str) -> Vec<&str> {
+    paragraph_spans(text)
+        .into_iter()
+        .map(|span| &text[span])
+        .collect()
+}
+
+/// Byte ranges of the paragraphs of a text, without surrounding whitespace.
+pub fn paragraph_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            spans.extend(trim_span(text, start..offset));
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    spans.extend(trim_span(text, start..text.len()));
+
+    spans
+}
+
+/// Range without surrounding whitespace, or `None` if nothing else is left.
+fn trim_span(text: &str, span: Range<usize>) -> Option<Range<usize>> {
+    let slice = &text[span.clone()];
+    let trimmed = slice.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let start = span.start + slice.len() - slice.trim_start().len();
+    Some(start..start + trimmed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paragraphs_test() {
+        let text = "First paragraph.\nStill first.\n\n  \nSecond paragraph.\r\n\r\nThird.\n";
+        let expected = vec![
+            "First paragraph.\nStill first.",
+            "Second paragraph.",
+            "Third.",
+        ];
+
+        assert_eq!(paragraphs(text), expected);
+        assert_eq!(paragraph_spans(text)[1], 34..51);
+    }
+}
@@ -0,0 +1,1057 @@
+//! Expansion of numbers, symbols and abbreviations into the words they are spoken as.
+
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+use crate::Language;
+
+static ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+static TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+static SCALES: [&str; 7] = [
+    "",
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+];
+
+static MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Numbers with more digits are read digit by digit.
+static MAX_CARDINAL_DIGITS: usize = 15;
+
+/// A currency with the singular and plural of its unit and subunit.
+struct Currency {
+    symbol: &'static str,
+    unit: (&'static str, &'static str),
+    subunit: Option<(&'static str, &'static str)>,
+}
+
+static CURRENCIES: [Currency; 4] = [
+    Currency {
+        symbol: "$",
+        unit: ("dollar", "dollars"),
+        subunit: Some(("cent", "cents")),
+    },
+    Currency {
+        symbol: "£",
+        unit: ("pound", "pounds"),
+        subunit: Some(("penny", "pence")),
+    },
+    Currency {
+        symbol: "€",
+        unit: ("euro", "euros"),
+        subunit: Some(("cent", "cents")),
+    },
+    Currency {
+        symbol: "¥",
+        unit: ("yen", "yen"),
+        subunit: None,
+    },
+];
+
+/// Unit symbols with their singular and plural in American English. British spellings are derived from them.
+static UNITS: [(&str, &str, &str); 37] = [
+    ("km/h", "kilometer per hour", "kilometers per hour"),
+    ("kph", "kilometer per hour", "kilometers per hour"),
+    ("mph", "mile per hour", "miles per hour"),
+    ("km", "kilometer", "kilometers"),
+    ("cm", "centimeter", "centimeters"),
+    ("mm", "millimeter", "millimeters"),
+    ("m", "meter", "meters"),
+    ("mi", "mile", "miles"),
+    ("yd", "yard", "yards"),
+    ("ft", "foot", "feet"),
+    ("kg", "kilogram", "kilograms"),
+    ("mg", "milligram", "milligrams"),
+    ("g", "gram", "grams"),
+    ("lbs", "pound", "pounds"),
+    ("lb", "pound", "pounds"),
+    ("oz", "ounce", "ounces"),
+    ("ml", "milliliter", "milliliters"),
+    ("mL", "milliliter", "milliliters"),
+    ("l", "liter", "liters"),
+    ("L", "liter", "liters"),
+    ("°C", "degree Celsius", "degrees Celsius"),
+    ("°F", "degree Fahrenheit", "degrees Fahrenheit"),
+    ("°", "degree", "degrees"),
+    ("TB", "terabyte", "terabytes"),
+    ("GB", "gigabyte", "gigabytes"),
+    ("MB", "megabyte", "megabytes"),
+    ("KB", "kilobyte", "kilobytes"),
+    ("kB", "kilobyte", "kilobytes"),
+    ("GHz", "gigahertz", "gigahertz"),
+    ("MHz", "megahertz", "megahertz"),
+    ("kHz", "kilohertz", "kilohertz"),
+    ("Hz", "hertz", "hertz"),
+    ("kWh", "kilowatt hour", "kilowatt hours"),
+    ("kW", "kilowatt", "kilowatts"),
+    ("ms", "millisecond", "milliseconds"),
+    ("min", "minute", "minutes"),
+    ("sec", "second", "seconds"),
+];
+
+/// Units that are only recognized when attached to the number, since they are common words or letters.
+static ATTACHED_UNITS: [(&str, &str, &str); 5] = [
+    ("h", "hour", "hours"),
+    ("s", "second", "seconds"),
+    ("in", "inch", "inches"),
+    ("W", "watt", "watts"),
+    ("V", "volt", "volts"),
+];
+
+/// Abbreviations followed by a name, like "Dr. Watson". The period is optional.
+static TITLES: [(&str, &str); 8] = [
+    ("Mr", "Mister"),
+    ("Mrs", "Missus"),
+    ("Ms", "Miz"),
+    ("Dr", "Doctor"),
+    ("Prof", "Professor"),
+    ("St", "Saint"),
+    ("Mt", "Mount"),
+    ("Capt", "Captain"),
+];
+
+/// Abbreviations that always end with a period.
+static ABBREVIATIONS: [(&str, &str); 14] = [
+    ("e.g.", "for example"),
+    ("i.e.", "that is"),
+    ("etc.", "et cetera"),
+    ("vs.", "versus"),
+    ("approx.", "approximately"),
+    ("St.", "Street"),
+    ("Ave.", "Avenue"),
+    ("Blvd.", "Boulevard"),
+    ("Jr.", "Junior"),
+    ("Sr.", "Senior"),
+    ("Inc.", "Incorporated"),
+    ("Ltd.", "Limited"),
+    ("Corp.", "Corporation"),
+    ("Dept.", "Department"),
+];
+
+/// A number with optional thousands separators and decimals, like 1,234.5.
+static NUMBER: &str = r"\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?";
+
+static TITLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    let titles: Vec<&str> = TITLES.iter().map(|(abbr, _)| *abbr).collect();
+    Regex::new(&format!(r"\b({})\.?(\s+)([A-Z])", titles.join("|")))
+        .expect("Regex for titles is not valid.")
+});
+
+static ABBREVIATION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    let abbreviations: Vec<String> = ABBREVIATIONS
+        .iter()
+        .map(|(abbr, _)| regex::escape(abbr))
+        .collect();
+    Regex::new(&format!(r"\b({})", abbreviations.join("|")))
+        .expect("Regex for abbreviations is not valid.")
+});
+
+static NUMBER_SIGN_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:No|Nr)\.\s?(\d)|#(\d)").expect("Regex for number signs is not valid.")
+});
+
+static ISO_DATE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})\b").expect("Regex for dates is not valid.")
+});
+
+static SLASH_DATE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{1,2})/(\d{1,2})/(\d{4})\b").expect("Regex for dates is not valid.")
+});
+
+static MONTH_DAY_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"\b({})\s+(\d{{1,2}})(?:st|nd|rd|th)?\b(?:,?\s+(\d{{4}})\b)?",
+        MONTHS.join("|")
+    ))
+    .expect("Regex for dates is not valid.")
+});
+
+static DAY_MONTH_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"\b(\d{{1,2}})(?:st|nd|rd|th)?\s+(?:of\s+)?({})\b(?:,?\s+(\d{{4}})\b)?",
+        MONTHS.join("|")
+    ))
+    .expect("Regex for dates is not valid.")
+});
+
+static TIME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(\d{1,2})(?::(\d{2}))?(?::(\d{2}))?(?:\s?([ap])\.?m\b\.?)|\b(\d{1,2}):(\d{2})(?::(\d{2}))?\b")
+        .expect("Regex for times is not valid.")
+});
+
+static CURRENCY_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"([$£€¥])\s?({NUMBER})(?:\s?(thousand|million|billion|trillion|bn|[kKmMbB])\b)?"
+    ))
+    .expect("Regex for currencies is not valid.")
+});
+
+static PERCENT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"({NUMBER})\s?%")).expect("Regex for percentages is not valid.")
+});
+
+static UNIT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    let units: Vec<String> = UNITS
+        .iter()
+        .map(|(unit, _, _)| regex::escape(unit))
+        .collect();
+    let attached: Vec<String> = ATTACHED_UNITS
+        .iter()
+        .map(|(unit, _, _)| regex::escape(unit))
+        .collect();
+    Regex::new(&format!(
+        r"\b({NUMBER})(?:\s?({})|({}))(?:\b|$|\s)",
+        units.join("|"),
+        attached.join("|")
+    ))
+    .expect("Regex for units is not valid.")
+});
+
+static FRACTION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{1,3})/(\d{1,2})\b").expect("Regex for fractions is not valid.")
+});
+
+/// Decades like 1980s or '90s. Without century or apostrophe, like 30s, they are read as seconds.
+static DECADE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(1[1-9]|20)(\d)0s\b|['’](\d)0s\b").expect("Regex for decades is not valid.")
+});
+
+/// Years that follow words like "in" or a month.
+static YEAR_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"\b(in|In|since|Since|until|Until|by|By|from|From|circa|year|before|after|{})\s+(1[1-9]\d\d|20\d\d)\b",
+        MONTHS.join("|")
+    ))
+    .expect("Regex for years is not valid.")
+});
+
+static ORDINAL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(\d{1,3}(?:,\d{3})+|\d+)(st|nd|rd|th)\b")
+        .expect("Regex for ordinals is not valid.")
+});
+
+/// Ranges like 1-2 or 10–20.
+static RANGE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{1,4})\s?[-–]\s?(\d{1,4})\b").expect("Regex for ranges is not valid.")
+});
+
+static NUMBER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"([-−])?({NUMBER})")).expect("Regex for numbers is not valid.")
+});
+
+/// Expand numbers, dates, times, currencies, percentages, units and common abbreviations into words, so that
+/// they can be phonemized. Dates, spellings and the use of "and" in numbers follow the given language. Text in
+/// languages other than English is returned as it is.
+pub fn normalize(text: &str, language: Language) -> String {
+    if !language.is_english() {
+        return text.to_owned();
+    }
+
+    let text = expand_abbreviations(text);
+    let text = NUMBER_SIGN_REGEX.replace_all(&text, |caps: &Captures| {
+        let digit = caps.get(1).or(caps.get(2)).unwrap().as_str();
+        format!("number {digit}")
+    });
+    let text = expand_dates(&text, language);
+    let text = expand_times(&text, language);
+    let text = expand_currencies(&text, language);
+    let text = expand_ranges(&text, language);
+    let text = PERCENT_REGEX.replace_all(&text, |caps: &Captures| {
+        let percent = match language {
+            Language::EnGb => "per cent",
+            _ => "percent",
+        };
+        format!("{} {percent}", number_words(&caps[1], language))
+    });
+    let text = DECADE_REGEX.replace_all(&text, |caps: &Captures| {
+        let decade = match caps.get(1) {
+            Some(century) => year(
+                format!("{}{}0", century.as_str(), &caps[2])
+                    .parse()
+                    .unwrap(),
+                language,
+            ),
+            None => cardinal(caps[3].parse::<u64>().unwrap() * 10, language),
+        };
+        plural(&decade)
+    });
+    let text = expand_units(&text, language);
+    let text = FRACTION_REGEX.replace_all(&text, |caps: &Captures| {
+        fraction(&caps[1], &caps[2], language)
+    });
+    let text = YEAR_REGEX.replace_all(&text, |caps: &Captures| {
+        format!("{} {}", &caps[1], year(caps[2].parse().unwrap(), language))
+    });
+    let text = ORDINAL_REGEX.replace_all(&text, |caps: &Captures| {
+        ordinal(&integer_words(&caps[1], language))
+    });
+    let text = expand_numbers(&text, language);
+
+    text.replace(" & ", " and ")
+}
+
+fn expand_numbers(text: &str, language: Language) -> String {
+    NUMBER_REGEX
+        .replace_all(text, |caps: &Captures| {
+            let matched = caps.get(0).unwrap();
+            let before = text[..matched.start()].chars().next_back();
+            let after = text[matched.end()..].chars().next();
+            let mut number = number_words(&caps[2], language);
+
+            // Only a dash in front of a number is read as a sign, not one between words or numbers
+            match caps.get(1) {
+                Some(_) if before.is_none_or(|c| c.is_whitespace() || matches!(c, '(' | '[')) => {
+                    number = format!("minus {number}");
+                }
+                Some(sign) => number = format!("{}{number}", sign.as_str()),
+                None => {}
+            }
+
+            // Numbers within words, like mp3, are separated from the letters
+            if before.is_some_and(char::is_alphabetic) && caps.get(1).is_none() {
+                number = format!(" {number}");
+            }
+            if after.is_some_and(char::is_alphabetic) {
+                number.push(' ');
+            }
+
+            number
+        })
+        .into_owned()
+}
+
+fn expand_ranges(text: &str, language: Language) -> String {
+    RANGE_REGEX
+        .replace_all(text, |caps: &Captures| {
+            let matched = caps.get(0).unwrap();
+            let before = text[..matched.start()].chars().next_back();
+            let after = &text[matched.end()..];
+            let from: u64 = caps[1].parse().unwrap();
+            let to: u64 = caps[2].parse().unwrap();
+
+            // Scores like 3-1 and codes like 555-123-4567 are not ranges
+            if from >= to
+                || before.is_some_and(|c| matches!(c, '-' | '–' | '−'))
+                || after.starts_with(['-', '–'])
+            {
+                return caps[0].to_owned();
+            }
+            format!(
+                "{} to {}",
+                number_words(&caps[1], language),
+                number_words(&caps[2], language)
+            )
+        })
+        .into_owned()
+}
+
+fn expand_abbreviations(text: &str) -> String {
+    let text = TITLE_REGEX.replace_all(text, |caps: &Captures| {
+        let title = TITLES.iter().find(|(abbr, _)| *abbr == &caps[1]).unwrap().1;
+
+        // "St." after a name like "Main" is a street, unless the name starts the text
+        let before = text[..caps.get(0).unwrap().start()].trim_end();
+        let previous_word = before
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default();
+        if &caps[1] == "St"
+            && previous_word.starts_with(char::is_uppercase)
+            && previous_word.len() < before.len()
+        {
+            let period = if caps[0].contains('.') { "." } else { "" };
+            return format!("Street{period}{}{}", &caps[2], &caps[3]);
+        }
+
+        format!("{title}{}{}", &caps[2], &caps[3])
+    });
+
+    ABBREVIATION_REGEX
+        .replace_all(&text, |caps: &Captures| {
+            let expansion = ABBREVIATIONS
+                .iter()
+                .find(|(abbr, _)| *abbr == &caps[1])
+                .unwrap()
+                .1;
+
+            // The period of the abbreviation can also end the sentence
+            let rest = &text[caps.get(0).unwrap().end()..];
+            if ends_sentence(rest) {
+                format!("{expansion}.")
+            } else {
+                expansion.to_owned()
+            }
+        })
+        .into_owned()
+}
+
+/// Whether text following a period starts a new sentence.
+fn ends_sentence(rest: &str) -> bool {
+    let trimmed = rest.trim_start();
+    trimmed.is_empty()
+        || (trimmed.len() < rest.len() && trimmed.starts_with(|c: char| c.is_uppercase()))
+}
+
+fn expand_dates(text: &str, language: Language) -> String {
+    let text = ISO_DATE_REGEX.replace_all(text, |caps: &Captures| {
+        date(&caps[3], &caps[2], Some(&caps[1]), language)
+            .unwrap_or_else(|| caps[0].replace('-', " "))
+    });
+    let text = SLASH_DATE_REGEX.replace_all(&text, |caps: &Captures| {
+        let (day, month) = match language {
+            Language::EnGb => (&caps[1], &caps[2]),
+            _ => (&caps[2], &caps[1]),
+        };
+        // Invalid dates are read as the numbers they consist of
+        date(day, month, Some(&caps[3]), language).unwrap_or_else(|| caps[0].replace('/', " "))
+    });
+    let text = MONTH_DAY_REGEX.replace_all(&text, |caps: &Captures| {
+        let month = MONTHS.iter().position(|m| *m == &caps[1]).unwrap() + 1;
+        let year = caps.get(3).map(|y| y.as_str());
+        date(&caps[2], &month.to_string(), year, language).unwrap_or_else(|| caps[0].to_owned())
+    });
+
+    // Dates like "1 May" keep their order
+    DAY_MONTH_REGEX
+        .replace_all(&text, |caps: &Captures| {
+            let day: u64 = caps[1].parse().unwrap();
+            if !(1..=31).contains(&day) {
+                return caps[0].to_owned();
+            }
+
+            let day = ordinal(&cardinal(day, language));
+            match caps.get(3) {
+                Some(y) => format!(
+                    "the {day} of {}, {}",
+                    &caps[2],
+                    year(y.as_str().parse().unwrap(), language)
+                ),
+                None => format!("the {day} of {}", &caps[2]),
+            }
+        })
+        .into_owned()
+}
+
+/// Spoken date, in the order common for the language. Returns `None` for invalid days or months.
+pub fn date(day: &str, month: &str, year_digits: Option<&str>, language: Language) -> Option<String> {
+    let day: u64 = day.parse().ok()?;
+    let month: usize = month.parse().ok()?;
+    if !(1..=31).contains(&day) || !(1..=12).contains(&month) {
+        return None;
+    }
+
+    let day = ordinal(&cardinal(day, language));
+    let month = MONTHS[month - 1];
+    let date = match language {
+        Language::EnGb => format!("the {day} of {month}"),
+        _ => format!("{month} {day}"),
+    };
+
+    Some(match year_digits {
+        Some(digits) => format!("{date}, {}", year(digits.parse().ok()?, language)),
+        None => date,
+    })
+}
+
+fn expand_times(text: &str, language: Language) -> String {
+    TIME_REGEX
+        .replace_all(text, |caps: &Captures| {
+            let (hours, minutes, seconds, meridiem) = match caps.get(1) {
+                Some(hours) => (hours, caps.get(2), caps.get(3), caps.get(4)),
+                None => (caps.get(5).unwrap(), caps.get(6), caps.get(7), None),
+            };
+            let hours: u64 = hours.as_str().parse().unwrap();
+            let minutes: u64 = minutes.map_or(Ok(0), |m| m.as_str().parse()).unwrap();
+            let max_hours = if meridiem.is_some() { 12 } else { 23 };
+            if hours > max_hours || minutes > 59 {
+                return caps[0].replace(':', " ");
+            }
+
+            let mut time = cardinal(hours, language);
+            match minutes {
+                0 if meridiem.is_some() => {}
+                0 if hours <= 12 => time += " o'clock",
+                0 => time += " hundred",
+                1..10 => time += &format!(" oh {}", cardinal(minutes, language)),
+                _ => time += &format!(" {}", cardinal(minutes, language)),
+            }
+            if let Some(seconds) = seconds {
+                let seconds: u64 = seconds.as_str().parse().unwrap();
+                time += &format!(
+                    " and {} {}",
+                    cardinal(seconds, language),
+                    if seconds == 1 { "second" } else { "seconds" }
+                );
+            }
+            if let Some(meridiem) = meridiem {
+                time += match meridiem.as_str() {
+                    "a" | "A" => " ay em",
+                    _ => " pee em",
+                };
+
+                // The matched period can also end the sentence
+                let end = caps.get(0).unwrap().end();
+                if caps[0].ends_with('.') && ends_sentence(&text[end..]) {
+                    time.push('.');
+                }
+            }
+
+            time
+        })
+        .into_owned()
+}
+
+fn expand_currencies(text: &str, language: Language) -> String {
+    CURRENCY_REGEX
+        .replace_all(text, |caps: &Captures| {
+            let currency = CURRENCIES
+                .iter()
+                .find(|currency| currency.symbol == &caps[1])
+                .unwrap();
+            let (unit, units) = currency.unit;
+            let amount = &caps[2];
+
+            // Amounts like $2.5 million
+            if let Some(scale) = caps.get(3) {
+                let scale = match scale.as_str() {
+                    "k" | "K" => "thousand",
+                    "m" | "M" => "million",
+                    "b" | "B" | "bn" => "billion",
+                    scale => scale,
+                };
+                return format!("{} {scale} {units}", number_words(amount, language));
+            }
+
+            let (integer, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+            let major = format!(
+                "{} {}",
+                integer_words(integer, language),
+                if parse_integer(integer) == Some(1) {
+                    unit
+                } else {
+                    units
+                }
+            );
+
+            match (fraction.len(), currency.subunit) {
+                (0, _) => major,
+                (2, Some((subunit, subunits))) => {
+                    let minor: u64 = fraction.parse().unwrap();
+                    let minor = format!(
+                        "{} {}",
+                        cardinal(minor, language),
+                        if minor == 1 { subunit } else { subunits }
+                    );
+                    match (parse_integer(integer), fraction) {
+                        (_, "00") => major,
+                        (Some(0), _) => minor,
+                        _ => format!("{major} and {minor}"),
+                    }
+                }
+                _ => format!("{} {units}", number_words(amount, language)),
+            }
+        })
+        .into_owned()
+}
+
+fn expand_units(text: &str, language: Language) -> String {
+    UNIT_REGEX
+        .replace_all(text, |caps: &Captures| {
+            let symbol = caps.get(2).or(caps.get(3)).unwrap().as_str();
+            let (_, singular, plural) = UNITS
+                .iter()
+                .chain(ATTACHED_UNITS.iter())
+                .find(|(unit, ..)| *unit == symbol)
+                .unwrap();
+
+            let amount = &caps[1];
+            let name = if amount == "1" { singular } else { plural };
+            let name = match language {
+                Language::EnGb => name.replace("meter", "metre").replace("liter", "litre"),
+                _ => name.to_string(),
+            };
+
+            // Keep the whitespace that ended the match
+            let trailing = if caps[0].ends_with(char::is_whitespace) {
+                &caps[0][caps[0].trim_end().len()..]
+            } else {
+                ""
+            };
+
+            format!("{} {name}{trailing}", number_words(amount, language))
+        })
+        .into_owned()
+}
+
+/// Spoken fraction like "two thirds". Denominators that are rarely used in fractions are read like "one over
+/// fifty".
+fn fraction(numerator: &str, denominator: &str, language: Language) -> String {
+    let numerator: u64 = numerator.parse().unwrap();
+    let denominator: u64 = denominator.parse().unwrap();
+    if !(2..=10).contains(&denominator) {
+        return format!(
+            "{} over {}",
+            cardinal(numerator, language),
+            cardinal(denominator, language)
+        );
+    }
+
+    let denominator = match denominator {
+        2 => "half".to_owned(),
+        4 => "quarter".to_owned(),
+        n => ordinal(&cardinal(n, language)),
+    };
+    let denominator = match (numerator, denominator.as_str()) {
+        (1, _) => denominator,
+        (_, "half") => "halves".to_owned(),
+        _ => format!("{denominator}s"),
+    };
+
+    format!("{} {denominator}", cardinal(numerator, language))
+}
+
+/// Words for a number with optional thousands separators and decimals.
+fn number_words(number: &str, language: Language) -> String {
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let digits = integer.replace(',', "");
+
+    // Long numbers, and numbers with leading zeros like codes, are read digit by digit
+    let mut words =
+        if digits.len() > MAX_CARDINAL_DIGITS || (digits.len() > 1 && digits.starts_with('0')) {
+            digit_words(&digits)
+        } else {
+            cardinal(digits.parse().unwrap(), language)
+        };
+
+    if !fraction.is_empty() {
+        words += " point ";
+        words += &digit_words(fraction);
+    }
+    words
+}
+
+fn digit_words(digits: &str) -> String {
+    digits
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|d| ONES[d as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses a whole number with optional thousands separators, or `None` if it's too long to read as a cardinal.
+fn parse_integer(digits: &str) -> Option<u64> {
+    let digits = digits.replace(',', "");
+    digits
+        .parse()
+        .ok()
+        .filter(|_| digits.len() <= MAX_CARDINAL_DIGITS)
+}
+
+/// Words for a whole number, read digit by digit if it's too long for a cardinal.
+fn integer_words(digits: &str, language: Language) -> String {
+    match parse_integer(digits) {
+        Some(number) => cardinal(number, language),
+        None => digit_words(digits),
+    }
+}
+
+/// Words for a whole number, like "one hundred twenty-three". British English adds "and" before the tens, like
+/// "one hundred and twenty-three".
+pub fn cardinal(number: u64, language: Language) -> String {
+    if number == 0 {
+        return ONES[0].to_owned();
+    }
+
+    // Groups of three digits, starting with the lowest
+    let mut groups = Vec::new();
+    let mut rest = number;
+    while rest > 0 {
+        groups.push(rest % 1000);
+        rest /= 1000;
+    }
+
+    let mut parts = Vec::new();
+    for (scale, &group) in groups.iter().enumerate().rev() {
+        if group == 0 {
+            continue;
+        }
+
+        let mut words = hundreds(group, language);
+        // "one thousand and five"
+        if language == Language::EnGb && scale == 0 && group < 100 && number >= 1000 {
+            words = format!("and {words}");
+        }
+        if scale > 0 {
+            words = format!("{words} {}", SCALES[scale]);
+        }
+        parts.push(words);
+    }
+
+    parts.join(" ")
+}
+
+/// Words for a number below 1000.
+fn hundreds(number: u64, language: Language) -> String {
+    let (hundreds, rest) = (number / 100, number % 100);
+    let tens = match rest {
+        0 => String::new(),
+        1..20 => ONES[rest as usize].to_owned(),
+        _ if rest % 10 == 0 => TENS[(rest / 10) as usize].to_owned(),
+        _ => format!(
+            "{}-{}",
+            TENS[(rest / 10) as usize],
+            ONES[(rest % 10) as usize]
+        ),
+    };
+
+    match (hundreds, rest) {
+        (0, _) => tens,
+        (_, 0) => format!("{} hundred", ONES[hundreds as usize]),
+        _ => match language {
+            Language::EnGb => format!("{} hundred and {tens}", ONES[hundreds as usize]),
+            _ => format!("{} hundred {tens}", ONES[hundreds as usize]),
+        },
+    }
+}
+
+/// Turn the words of a cardinal into the ordinal, like "twenty-one" into "twenty-first".
+pub fn ordinal(cardinal: &str) -> String {
+    let split = cardinal.rfind([' ', '-']).map_or(0, |i| i + 1);
+    let (head, last) = cardinal.split_at(split);
+
+    let last = match last {
+        "one" => "first".to_owned(),
+        "two" => "second".to_owned(),
+        "three" => "third".to_owned(),
+        "five" => "fifth".to_owned(),
+        "eight" => "eighth".to_owned(),
+        "nine" => "ninth".to_owned(),
+        "twelve" => "twelfth".to_owned(),
+        last if last.ends_with('y') => format!("{}ieth", &last[..last.len() - 1]),
+        last => format!("{last}th"),
+    };
+    format!("{head}{last}")
+}
+
+/// Words for a year, like "nineteen ninety-nine" or "two thousand five".
+pub fn year(year: u64, language: Language) -> String {
+    if !(1000..10000).contains(&year) || (2000..2010).contains(&year) || year.is_multiple_of(1000) {
+        return cardinal(year, language);
+    }
+
+    let century = cardinal(year / 100, language);
+    match year % 100 {
+        0 => format!("{century} hundred"),
+        rest @ 1..10 => format!("{century} oh {}", cardinal(rest, language)),
+        rest => format!("{century} {}", cardinal(rest, language)),
+    }
+}
+
+/// Plural of number words, like "nineties" for "ninety".
+fn plural(words: &str) -> String {
+    match words.strip_suffix('y') {
+        Some(stem) => format!("{stem}ies"),
+        None => format!("{words}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Language::{EnGb, EnUs};
+
+    #[test]
+    fn cardinal_test() {
+        let cases = [
+            (0, EnUs, "zero"),
+            (7, EnUs, "seven"),
+            (13, EnUs, "thirteen"),
+            (40, EnUs, "forty"),
+            (42, EnUs, "forty-two"),
+            (100, EnUs, "one hundred"),
+            (101, EnUs, "one hundred one"),
+            (101, EnGb, "one hundred and one"),
+            (999, EnUs, "nine hundred ninety-nine"),
+            (1000, EnUs, "one thousand"),
+            (1005, EnUs, "one thousand five"),
+            (1005, EnGb, "one thousand and five"),
+            (1234, EnGb, "one thousand two hundred and thirty-four"),
+            (20_000, EnUs, "twenty thousand"),
+            (1_000_001, EnGb, "one million and one"),
+            (
+                1_234_567,
+                EnUs,
+                "one million two hundred thirty-four thousand five hundred sixty-seven",
+            ),
+            (3_000_000_000, EnUs, "three billion"),
+        ];
+
+        for (number, language, expected) in cases {
+            assert_eq!(
+                cardinal(number, language),
+                expected,
+                "{number} in {language:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ordinal_and_year_test() {
+        let ordinals = [
+            ("one", "first"),
+            ("twelve", "twelfth"),
+            ("twenty", "twentieth"),
+            ("twenty-three", "twenty-third"),
+            ("one hundred and one", "one hundred and first"),
+            ("one thousand", "one thousandth"),
+        ];
+        for (cardinal, expected) in ordinals {
+            assert_eq!(ordinal(cardinal), expected);
+        }
+
+        let years = [
+            (1066, EnUs, "ten sixty-six"),
+            (1900, EnUs, "nineteen hundred"),
+            (1905, EnUs, "nineteen oh five"),
+            (1999, EnUs, "nineteen ninety-nine"),
+            (2000, EnUs, "two thousand"),
+            (2005, EnUs, "two thousand five"),
+            (2005, EnGb, "two thousand and five"),
+            (2010, EnUs, "twenty ten"),
+            (2024, EnGb, "twenty twenty-four"),
+            (800, EnUs, "eight hundred"),
+        ];
+        for (number, language, expected) in years {
+            assert_eq!(year(number, language), expected, "{number} in {language:?}");
+        }
+    }
+
+    #[test]
+    fn normalize_test() {
+        let cases = [
+            // Cardinals and decimals
+            ("I have 3 apples.", EnUs, "I have three apples."),
+            (
+                "It costs 1,250 points.",
+                EnUs,
+                "It costs one thousand two hundred fifty points.",
+            ),
+            (
+                "It costs 1,250 points.",
+                EnGb,
+                "It costs one thousand two hundred and fifty points.",
+            ),
+            ("Pi is 3.14.", EnUs, "Pi is three point one four."),
+            ("It's -5 outside.", EnUs, "It's minus five outside."),
+            ("Call 007.", EnUs, "Call zero zero seven."),
+            ("Pages 1-2", EnUs, "Pages one to two"),
+            ("ages 10–20", EnGb, "ages ten to twenty"),
+            ("It ended 3-1.", EnUs, "It ended three-one."),
+            ("(-3)", EnUs, "(minus three)"),
+            ("Numbers 1,2,3", EnUs, "Numbers one,two,three"),
+            // Ordinals
+            ("The 1st and 2nd place", EnUs, "The first and second place"),
+            ("Her 21st birthday", EnUs, "Her twenty-first birthday"),
+            ("The 101st time", EnGb, "The one hundred and first time"),
+            ("The 3RD row", EnUs, "The third row"),
+            // Currencies
+            ("$3.50", EnUs, "three dollars and fifty cents"),
+            ("$1", EnUs, "one dollar"),
+            ("$0.99", EnUs, "ninety-nine cents"),
+            ("$0.01", EnUs, "one cent"),
+            ("$5.00", EnUs, "five dollars"),
+            ("£20", EnGb, "twenty pounds"),
+            ("£1.50", EnGb, "one pound and fifty pence"),
+            ("€2.99", EnGb, "two euros and ninety-nine cents"),
+            ("¥500", EnUs, "five hundred yen"),
+            ("$2.5 million", EnUs, "two point five million dollars"),
+            ("$3M", EnUs, "three million dollars"),
+            ("£4bn", EnGb, "four billion pounds"),
+            ("$1,000,000", EnUs, "one million dollars"),
+            ("$1.234", EnUs, "one point two three four dollars"),
+            (
+                "$12345678901234567890",
+                EnUs,
+                "one two three four five six seven eight nine zero one two three four five six seven eight nine zero dollars",
+            ),
+            // Dates
+            ("on 2024-05-01", EnUs, "on May first, twenty twenty-four"),
+            (
+                "on 2024-05-01",
+                EnGb,
+                "on the first of May, twenty twenty-four",
+            ),
+            ("05/01/2024", EnUs, "May first, twenty twenty-four"),
+            (
+                "05/01/2024",
+                EnGb,
+                "the fifth of January, twenty twenty-four",
+            ),
+            (
+                "13/13/2024",
+                EnUs,
+                "thirteen thirteen two thousand twenty-four",
+            ),
+            ("July 4, 1776", EnUs, "July fourth, seventeen seventy-six"),
+            ("July 4th", EnUs, "July fourth"),
+            (
+                "the 12345678901234567th",
+                EnUs,
+                "the one two three four five six seven eight nine zero one two three four five six seventh",
+            ),
+            ("July 4", EnGb, "the fourth of July"),
+            ("1 May 2024", EnUs, "the first of May, twenty twenty-four"),
+            ("21st of June", EnGb, "the twenty-first of June"),
+            // Years and decades
+            ("Born in 1999.", EnUs, "Born in nineteen ninety-nine."),
+            ("Since 2005", EnGb, "Since two thousand and five"),
+            ("The 1980s", EnUs, "The nineteen eighties"),
+            ("The '90s", EnUs, "The nineties"),
+            ("in March 2020", EnUs, "in March twenty twenty"),
+            ("In the 2000s", EnUs, "In the two thousands"),
+            (
+                "It has 1999 pages.",
+                EnUs,
+                "It has one thousand nine hundred ninety-nine pages.",
+            ),
+            // Times
+            ("at 10:30", EnUs, "at ten thirty"),
+            ("at 10:05", EnUs, "at ten oh five"),
+            ("at 10:00", EnUs, "at ten o'clock"),
+            ("at 14:00", EnGb, "at fourteen hundred"),
+            ("at 9:15:30", EnUs, "at nine fifteen and thirty seconds"),
+            ("at 3pm", EnUs, "at three pee em"),
+            (
+                "at 7:45 a.m. today",
+                EnUs,
+                "at seven forty-five ay em today",
+            ),
+            ("It starts at 8 PM.", EnUs, "It starts at eight pee em."),
+            ("at 25:00", EnUs, "at twenty-five zero zero"),
+            // Percentages
+            ("50%", EnUs, "fifty percent"),
+            ("50%", EnGb, "fifty per cent"),
+            ("a 3.5 % rise", EnUs, "a three point five percent rise"),
+            // Units
+            ("5km", EnUs, "five kilometers"),
+            ("5 km away", EnGb, "five kilometres away"),
+            ("1 km", EnUs, "one kilometer"),
+            ("2.5 kg", EnUs, "two point five kilograms"),
+            ("100 km/h", EnUs, "one hundred kilometers per hour"),
+            ("60 mph", EnUs, "sixty miles per hour"),
+            ("6 ft", EnUs, "six feet"),
+            ("20°C", EnUs, "twenty degrees Celsius"),
+            ("a 90° angle", EnUs, "a ninety degrees angle"),
+            ("500 ml", EnGb, "five hundred millilitres"),
+            ("16 GB of RAM", EnUs, "sixteen gigabytes of RAM"),
+            ("3.2 GHz", EnUs, "three point two gigahertz"),
+            ("2h", EnUs, "two hours"),
+            ("2 h", EnUs, "two h"),
+            ("30s", EnUs, "thirty seconds"),
+            ("12in", EnUs, "twelve inches"),
+            ("5 in the box", EnUs, "five in the box"),
+            ("5 mins", EnUs, "five mins"),
+            // Fractions
+            ("1/2 cup", EnUs, "one half cup"),
+            ("3/4 of it", EnUs, "three quarters of it"),
+            ("2/3", EnUs, "two thirds"),
+            ("3/2", EnUs, "three halves"),
+            ("24/7", EnUs, "twenty-four sevenths"),
+            ("1/50", EnUs, "one over fifty"),
+            // Abbreviations
+            ("Dr. Smith", EnUs, "Doctor Smith"),
+            ("Mr Jones", EnGb, "Mister Jones"),
+            (
+                "Mrs. Brown and Ms. Green",
+                EnUs,
+                "Missus Brown and Miz Green",
+            ),
+            (
+                "St. Paul lives on Main St. in town",
+                EnUs,
+                "Saint Paul lives on Main Street in town",
+            ),
+            ("on Main St. Then", EnUs, "on Main Street. Then"),
+            (
+                "apples, pears, etc. are fruit",
+                EnUs,
+                "apples, pears, et cetera are fruit",
+            ),
+            ("apples, pears, etc.", EnUs, "apples, pears, et cetera."),
+            ("fruit, e.g. apples", EnUs, "fruit, for example apples"),
+            ("cats vs. dogs", EnUs, "cats versus dogs"),
+            ("Room No. 5", EnUs, "Room number five"),
+            ("Issue #12", EnUs, "Issue number twelve"),
+            ("Tom & Jerry", EnUs, "Tom and Jerry"),
+            ("Mr. 5", EnUs, "Mr. five"),
+            // Combined
+            (
+                "$3.50 on 2024-05-01 at 10:30, 5km",
+                EnUs,
+                "three dollars and fifty cents on May first, twenty twenty-four at ten thirty, five kilometers",
+            ),
+            (
+                "$3.50 on 2024-05-01 at 10:30, 5km",
+                EnGb,
+                "three dollars and fifty cents on the first of May, twenty twenty-four at ten thirty, five kilometres",
+            ),
+            // Text without numbers is left alone
+            ("Nothing to see here.", EnUs, "Nothing to see here."),
+            ("mp3 files", EnUs, "mp three files"),
+            ("a 4K screen", EnUs, "a four K screen"),
+        ];
+
+        for (text, language, expected) in cases {
+            assert_eq!(
+                normalize(text, language),
+                expected,
+                "{text:?} in {language:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Inline markup in the syntax of links, which controls how words within text are read:
+//! - `[Kokoro](/kˈOkəɹO/)` gives the phonemes of a word,
+//! - `[ABC-123](spell)` spells out letters and digits one by one, see [`spell`],
+//! - `[word](+)` and `[word](++)` emphasize words, with pauses around them for the latter, and `[word](-)`
+//!   de-emphasizes them, see [`emphasize`].
+
+use std::{fmt, ops::Range, sync::LazyLock};
+
+use anyhow::{Result, bail};
+use regex::Regex;
+
+use super::{normalize, spell::spell};
+use crate::{
+    Language, Phonemizer,
+    kokoro::tokenizer::KokoroTokenizer,
+    phonemes::{self, Emphasis, emphasize},
+};
+
+/// Words followed by their phonemes between slashes, or by how they are read.
+static OVERRIDE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[([^\[\]]+)\]\((?:/([^/()]+)/|(spell|\+\+|\+|-))\)")
+        .expect("Regex for pronunciation overrides is not valid.")
+});
+
+/// Part of a text, either to be phonemized or read as the markup says.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Span<'a> {
+    Text(&'a str),
+    Override { text: &'a str, phonemes: &'a str },
+    Spell(&'a str),
+    Emphasis { text: &'a str, emphasis: Emphasis },
+}
+
+/// Spans are displayed as the markup they were parsed from.
+impl fmt::Display for Span<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Span::Text(text) => write!(f, "{text}"),
+            Span::Override { text, phonemes } => write!(f, "[{text}](/{phonemes}/)"),
+            Span::Spell(text) => write!(f, "[{text}](spell)"),
+            Span::Emphasis { text, emphasis } => {
+                let level = match emphasis {
+                    Emphasis::Reduced => "-",
+                    Emphasis::Moderate => "+",
+                    Emphasis::Strong => "++",
+                };
+                write!(f, "[{text}]({level})")
+            }
+        }
+    }
+}
+
+/// Whether the destination of a link is markup of this module, like `/kˈOkəɹO/` or `spell`. Phonemes have to be
+/// accepted by [`spans`] and contain a stress mark or IPA symbol, so that paths like `/docs/` are kept as links.
+pub fn is_markup_destination(destination: &str) -> bool {
+    if matches!(destination, "spell" | "++" | "+" | "-") {
+        return true;
+    }
+
+    let Some(phonemes) = destination
+        .strip_prefix('/')
+        .and_then(|destination| destination.strip_suffix('/'))
+    else {
+        return false;
+    };
+    !phonemes.is_empty()
+        && !phonemes.is_ascii()
+        && !phonemes.contains(['/', '(', ')'])
+        && unknown_phoneme(phonemes).is_none()
+}
+
+/// First symbol of phonemes that Kokoro doesn't know, even after mapping them with [`phonemes::to_kokoro`].
+fn unknown_phoneme(phonemes: &str) -> Option<char> {
+    phonemes::to_kokoro(phonemes)
+        .chars()
+        .find(|c| !c.is_whitespace() && !KokoroTokenizer::is_known(*c))
+}
+
+/// Split text into markup and the text around it.
+///
+/// # Errors
+/// Fails if the phonemes of an override contain symbols Kokoro doesn't know, even after mapping them with
+/// [`phonemes::to_kokoro`].
+pub fn spans(text: &str) -> Result<Vec<Span<'_>>> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+
+    for captures in OVERRIDE_REGEX.captures_iter(text) {
+        let whole = captures.get(0).unwrap();
+        let word = captures.get(1).unwrap().as_str();
+
+        let span = match (captures.get(2), captures.get(3).map(|m| m.as_str())) {
+            (Some(word_phonemes), _) => {
+                let word_phonemes = word_phonemes.as_str();
+                if let Some(unknown) = unknown_phoneme(word_phonemes) {
+                    bail!("Unknown phoneme '{unknown}' in the pronunciation of '{word}'");
+                }
+
+                Span::Override {
+                    text: word,
+                    phonemes: word_phonemes,
+                }
+            }
+            (None, Some("spell")) => Span::Spell(word),
+            (None, Some(level)) => Span::Emphasis {
+                text: word,
+                emphasis: match level {
+                    "-" => Emphasis::Reduced,
+                    "+" => Emphasis::Moderate,
+                    _ => Emphasis::Strong,
+                },
+            },
+            (None, None) => unreachable!("the regex matches phonemes or a keyword"),
+        };
+
+        if whole.start() > last {
+            spans.push(Span::Text(&text[last..whole.start()]));
+        }
+        spans.push(span);
+        last = whole.end();
+    }
+    if last < text.len() {
+        spans.push(Span::Text(&text[last..]));
+    }
+
+    Ok(spans)
+}
+
+/// Byte ranges of the markup in a text.
+pub fn markup_ranges(text: &str) -> Vec<Range<usize>> {
+    OVERRIDE_REGEX.find_iter(text).map(|m| m.range()).collect()
+}
+
+/// Text with markup replaced by its words, e.g. for subtitles.
+pub fn strip(text: &str) -> String {
+    OVERRIDE_REGEX.replace_all(text, "$1").into_owned()
+}
+
+/// Phonemize text, using the phonemes of overrides instead of the phonemizer for them. Text between markup is
+/// phonemized separately. Letters are spelled out in the given language, and if `normalize` is set, numbers and
+/// abbreviations are expanded into its words with [`normalize::normalize`] first.
+pub fn phonemize<P: Phonemizer + ?Sized>(
+    text: &str,
+    phonemizer: &P,
+    language: Language,
+    normalize: bool,
+) -> Result<String> {
+    let phonemize_text = |text: &str| match normalize {
+        true => phonemizer.phonemize(&normalize::normalize(text, language)),
+        false => phonemizer.phonemize(text),
+    };
+
+    let mut result = String::new();
+    let mut space = false;
+    let mut pause = false;
+
+    for span in spans(text)? {
+        let (phonemes, starts_with_space, ends_with_space) = match span {
+            Span::Text(text) if text.trim().is_empty() => {
+                space = true;
+                continue;
+            }
+            Span::Text(text) => (
+                phonemize_text(text.trim())?,
+                text.starts_with(char::is_whitespace),
+                text.ends_with(char::is_whitespace),
+            ),
+            Span::Override { phonemes, .. } => (phonemes.to_owned(), false, false),
+            Span::Spell(text) => (
+                phonemize(&spell(text, language), phonemizer, language, normalize)?,
+                false,
+                false,
+            ),
+            Span::Emphasis { text, emphasis } => {
+                (emphasize(&phonemize_text(text)?, emphasis), false, false)
+            }
+        };
+
+        // Strongly emphasized words are set off by pauses, unless there is punctuation already
+        let phonemes = phonemes.trim();
+        let strong = matches!(
+            span,
+            Span::Emphasis {
+                emphasis: Emphasis::Strong,
+                ..
+            }
+        );
+        if (pause || strong)
+            && !result.is_empty()
+            && !result.ends_with(is_pause)
+            && !phonemes.starts_with(is_pause)
+        {
+            result.push(',');
+        }
+        pause = strong;
+
+        if !result.is_empty() && (space || starts_with_space) {
+            result.push(' ');
+        }
+        result.push_str(phonemes);
+        space = ends_with_space;
+    }
+
+    Ok(result)
+}
+
+fn is_pause(c: char) -> bool {
+    matches!(c, ',' | '.' | '!' | '?' | ';' | ':' | '—' | '…')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_test() -> Result<()> {
+        let text = "I like [Kokoro](/kˈOkəɹO/) and [New York](/nu jˈɔɹk/).";
+
+        assert_eq!(
+            spans(text)?,
+            vec![
+                Span::Text("I like "),
+                Span::Override {
+                    text: "Kokoro",
+                    phonemes: "kˈOkəɹO"
+                },
+                Span::Text(" and "),
+                Span::Override {
+                    text: "New York",
+                    phonemes: "nu jˈɔɹk"
+                },
+                Span::Text("."),
+            ]
+        );
+        assert_eq!(strip(text), "I like Kokoro and New York.");
+
+        // Links and unfinished overrides are kept as text
+        assert_eq!(
+            spans("[a](https://a.org) [b](/bˈi/")?,
+            vec![Span::Text("[a](https://a.org) [b](/bˈi/")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn markup_spans_test() -> Result<()> {
+        let text = "Code [ABC-123](spell), [really](++) [not](+) [that](-) [bad](+++).";
+        let spans = spans(text)?;
+
+        assert_eq!(
+            spans[..8],
+            [
+                Span::Text("Code "),
+                Span::Spell("ABC-123"),
+                Span::Text(", "),
+                Span::Emphasis {
+                    text: "really",
+                    emphasis: Emphasis::Strong
+                },
+                Span::Text(" "),
+                Span::Emphasis {
+                    text: "not",
+                    emphasis: Emphasis::Moderate
+                },
+                Span::Text(" "),
+                Span::Emphasis {
+                    text: "that",
+                    emphasis: Emphasis::Reduced
+                },
+            ]
+        );
+        assert_eq!(spans[8], Span::Text(" [bad](+++)."));
+        assert_eq!(spans.iter().map(Span::to_string).collect::<String>(), text);
+        assert_eq!(strip(text), "Code ABC-123, really not that [bad](+++).");
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_markup_destination_test() {
+        assert!(is_markup_destination("/təmˈɑːtəʊ/"));
+        assert!(is_markup_destination("spell"));
+        assert!(!is_markup_destination("/docs/"));
+        assert!(!is_markup_destination("/dˈɑks/api/"));
+        assert!(!is_markup_destination("/ˈjuzɚ_ɡˈId/"));
+        assert!(!is_markup_destination("/"));
+    }
+
+    #[test]
+    fn unknown_phonemes_test() {
+        assert!(spans("[word](/wɜːd/)").is_ok());
+        assert!(spans("[word](/wöd/)").is_err());
+        assert!(spans("[word](/w3d/)").is_err());
+    }
+
+    #[test]
+    fn phonemize_test() -> Result<()> {
+        let phonemizer = |text: &str| Ok(format!("<{text}>"));
+        let cases = [
+            (
+                "Say [tomato](/təmˈɑːtəʊ/) please.",
+                "<Say> təmˈɑːtəʊ <please.>",
+            ),
+            ("[Kokoro](/kˈOkəɹO/)'s voice", "kˈOkəɹO<'s voice>"),
+            ("[a](/ˈA/) [b](/bˈi/), c", "ˈA bˈi<, c>"),
+            ("No overrides.", "<No overrides.>"),
+            ("I have 2 [cats](/kˈæts/).", "<I have two> kˈæts<.>"),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(
+                phonemize(text, &phonemizer, Language::EnUs, true)?,
+                expected,
+                "{text:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn phonemize_markup_test() -> Result<()> {
+        let phonemizer = |text: &str| {
+            let words: Vec<String> = text
+                .split_whitespace()
+                .map(|word| {
+                    let core = word.trim_end_matches(is_pause);
+                    let phonemes = match core {
+                        "I" => "ˈI",
+                        "said" => "sˈɛd",
+                        "no" => "nˌO",
+                        "the" => "ðə",
+                        "two" => "tˈu",
+                        "today" => "tədˈA",
+                        _ => core,
+                    };
+                    format!("{phonemes}{}", &word[core.len()..])
+                })
+                .collect();
+            Ok(words.join(" "))
+        };
+        let cases = [
+            ("I said [no](++) today.", "ˈI sˈɛd, nˈO, tədˈA."),
+            ("I said, [no](++).", "ˈI sˈɛd, nˈO."),
+            ("[the](+) [I](-)", "ðˈə ˌI"),
+            ("[A2](spell)", "ˈeɪ, tˈu"),
+            ("[no](++) [no](++)!", "nˈO, nˈO!"),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(
+                phonemize(text, &phonemizer, Language::EnUs, true)?,
+                expected,
+                "{text:?}"
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+//! Splitting text into sentences, aware of abbreviations, decimals, ellipses and quotes.
+
+use std::ops::Range;
+
+use super::trim_span;
+
+/// Abbreviations that never end a sentence, since they are followed by a name or an example.
+static NON_FINAL_ABBREVIATIONS: [&str; 28] = [
+    "mr", "mrs", "ms", "mx", "dr", "prof", "st", "mt", "capt", "sen", "rep", "gen", "col", "lt",
+    "sgt", "rev", "hon", "gov", "pres", "fr", "e.g", "i.e", "vs", "cf", "approx", "ca", "viz",
+    "incl",
+];
+
+/// Abbreviations that don't end a sentence when followed by a number, like "No. 5".
+static NUMBER_ABBREVIATIONS: [&str; 9] = ["no", "nr", "nos", "vol", "fig", "pp", "p", "ch", "art"];
+
+/// Abbreviations that can end a sentence, which is assumed if the next word is capitalized.
+static AMBIGUOUS_ABBREVIATIONS: [&str; 28] = [
+    "etc", "inc", "ltd", "co", "corp", "jr", "sr", "bros", "dept", "est", "a.m", "p.m", "u.s",
+    "u.k", "ph.d", "jan", "feb", "mar", "apr", "jun", "jul", "aug", "sep", "sept", "oct", "nov",
+    "dec", "min",
+];
+
+/// Characters that end a sentence, possibly repeated like "?!" or "...".
+fn is_terminator(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '…' | '。' | '！' | '？')
+}
+
+/// Closing quotes and brackets, which belong to the sentence they follow.
+fn is_closing(c: char) -> bool {
+    matches!(
+        c,
+        '"' | '\'' | '”' | '’' | '»' | ')' | ']' | '」' | '』' | '）'
+    )
+}
+
+/// Opening quotes, brackets and the inverted marks that start Spanish sentences.
+fn is_opening(c: char) -> bool {
+    matches!(
+        c,
+        '"' | '\'' | '“' | '‘' | '«' | '(' | '[' | '¿' | '¡' | '「' | '『' | '（'
+    )
+}
+
+/// Split text into sentences. Empty sentences are skipped.
+pub fn sentences(text: &str) -> Vec<&str> {
+    sentence_spans(text)
+        .into_iter()
+        .map(|span| &text[span])
+        .collect()
+}
+
+/// Byte ranges of the sentences of a text, without surrounding whitespace.
+///
+/// A sentence ends with `.`, `!`, `?`, `…` or an interrupting `—` before a closing quote, followed by
+/// whitespace, or with the full-width `。`, `！` and `？` of Chinese and Japanese. Closing quotes and brackets stay
+/// with the sentence. It doesn't end if the next word starts in lowercase, like in `"Really?" he asked`, or if the
+/// period belongs to an abbreviation like "Dr." or an initial.
+pub fn sentence_spans(text: &str) -> Vec<Range<usize>> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    let mut i = 0;
+    while i < chars.len() {
+        let (run_start, c) = chars[i];
+        let interrupted = c == '—' && chars.get(i + 1).is_some_and(|&(_, next)| is_closing(next));
+        if !is_terminator(c) && !interrupted {
+            i += 1;
+            continue;
+        }
+
+        // Terminators and closing quotes, like `?!"`
+        while i < chars.len() && (is_terminator(chars[i].1) || chars[i].1 == '—') {
+            i += 1;
+        }
+        let terminators = &text[run_start..chars.get(i).map_or(text.len(), |&(idx, _)| idx)];
+        while i < chars.len() && is_closing(chars[i].1) {
+            i += 1;
+        }
+
+        // Chinese and Japanese don't put spaces between sentences
+        let end = chars.get(i).map_or(text.len(), |&(idx, _)| idx);
+        let full_width = terminators.contains(['。', '！', '？']);
+        if end < text.len() && !full_width && !text[end..].starts_with(char::is_whitespace) {
+            continue;
+        }
+
+        if is_boundary(&text[start..run_start], terminators, &text[end..]) {
+            spans.extend(trim_span(text, start..end));
+            start = end;
+        }
+    }
+    spans.extend(trim_span(text, start..text.len()));
+
+    spans
+}
+
+/// Whether the terminators after `before` end the sentence, given the text that follows.
+fn is_boundary(before: &str, terminators: &str, after: &str) -> bool {
+    let after = after.trim_start();
+    if after.is_empty() || after.starts_with(['¿', '¡']) {
+        return true;
+    }
+
+    // First letter or digit of the next sentence, behind any opening quotes
+    let Some(next) = after.chars().find(|c| !is_opening(*c)) else {
+        return true;
+    };
+    if next.is_lowercase() {
+        return false;
+    }
+
+    // Ellipses only end a sentence before a capitalized word
+    if terminators != "." {
+        return !terminators.contains(['…', '.']) || next.is_uppercase();
+    }
+
+    let word = before
+        .rsplit(|c: char| c.is_whitespace() || is_opening(c))
+        .next()
+        .unwrap_or_default();
+    let lowercase = word.to_lowercase();
+    let is_initial = {
+        let mut letters = word.chars();
+        matches!((letters.next(), letters.next()), (Some(c), None) if c.is_uppercase() && c != 'I')
+    };
+
+    if is_initial || NON_FINAL_ABBREVIATIONS.contains(&lowercase.as_str()) {
+        false
+    } else if NUMBER_ABBREVIATIONS.contains(&lowercase.as_str()) {
+        !next.is_ascii_digit()
+    } else if AMBIGUOUS_ABBREVIATIONS.contains(&lowercase.as_str()) || word.contains('.') {
+        next.is_uppercase()
+    } else {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentences_test() {
+        let cases: [(&str, &[&str]); 24] = [
+            (
+                "Hello there! How are you? I'm fine.Really.",
+                &["Hello there!", "How are you?", "I'm fine.Really."],
+            ),
+            (
+                "Dr. Smith arrived. He was late.",
+                &["Dr. Smith arrived.", "He was late."],
+            ),
+            (
+                "Mrs. and Mr. Jones are here.",
+                &["Mrs. and Mr. Jones are here."],
+            ),
+            (
+                "Fruit, e.g. apples, is healthy.",
+                &["Fruit, e.g. apples, is healthy."],
+            ),
+            (
+                "Fruit, i.e. Apples, is healthy.",
+                &["Fruit, i.e. Apples, is healthy."],
+            ),
+            (
+                "Pi is 3.14. It is irrational.",
+                &["Pi is 3.14.", "It is irrational."],
+            ),
+            ("It cost $3.50 in total.", &["It cost $3.50 in total."]),
+            (
+                "See No. 5 and vol. 2 for more.",
+                &["See No. 5 and vol. 2 for more."],
+            ),
+            ("I said no. Then I left.", &["I said no.", "Then I left."]),
+            (
+                "Apples, pears, etc. are fruit.",
+                &["Apples, pears, etc. are fruit."],
+            ),
+            (
+                "Apples, pears, etc. Then dessert.",
+                &["Apples, pears, etc.", "Then dessert."],
+            ),
+            ("It's in the U.S. now.", &["It's in the U.S. now."]),
+            (
+                "J. R. R. Tolkien wrote it.",
+                &["J. R. R. Tolkien wrote it."],
+            ),
+            ("So did I. Then we left.", &["So did I.", "Then we left."]),
+            ("Wait... what? No!", &["Wait... what?", "No!"]),
+            ("Well… Maybe.", &["Well…", "Maybe."]),
+            ("Really?! Yes.", &["Really?!", "Yes."]),
+            (
+                "He said \"Stop.\" Then he left.",
+                &["He said \"Stop.\"", "Then he left."],
+            ),
+            (
+                "\"Really?\" he asked. \"Yes.\"",
+                &["\"Really?\" he asked.", "\"Yes.\""],
+            ),
+            ("¿Qué? ¡Hola! Adiós.", &["¿Qué?", "¡Hola!", "Adiós."]),
+            (
+                "“I was just—” “Stop!” (It ended.) Fine",
+                &["“I was just—”", "“Stop!”", "(It ended.)", "Fine"],
+            ),
+            ("你好。我很好！你呢？", &["你好。", "我很好！", "你呢？"]),
+            ("「はい。」そうです。", &["「はい。」", "そうです。"]),
+            ("  \n  ", &[]),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(sentences(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn sentence_spans_test() {
+        let text = " Größe… Wie? Gut.\n";
+        let spans = sentence_spans(text);
+
+        assert_eq!(spans, vec![1..11, 12..16, 17..21]);
+        assert_eq!(&text[spans[0].clone()], "Größe…");
+    }
+}
@@ -0,0 +1,90 @@
+//! Spelling out text letter by letter, like codes and acronyms.
+
+use crate::Language;
+
+use super::normalize::cardinal;
+
+/// Phonemes of the names of the letters a to z, American and British.
+static LETTERS: [(&str, &str); 26] = [
+    ("ˈeɪ", "ˈeɪ"),
+    ("bˈi", "bˈiː"),
+    ("sˈi", "sˈiː"),
+    ("dˈi", "dˈiː"),
+    ("ˈi", "ˈiː"),
+    ("ˈɛf", "ˈɛf"),
+    ("ʤˈi", "ʤˈiː"),
+    ("ˈeɪʧ", "ˈeɪʧ"),
+    ("ˈaɪ", "ˈaɪ"),
+    ("ʤˈeɪ", "ʤˈeɪ"),
+    ("kˈeɪ", "kˈeɪ"),
+    ("ˈɛl", "ˈɛl"),
+    ("ˈɛm", "ˈɛm"),
+    ("ˈɛn", "ˈɛn"),
+    ("ˈoʊ", "ˈəʊ"),
+    ("pˈi", "pˈiː"),
+    ("kjˈu", "kjˈuː"),
+    ("ˈɑɹ", "ˈɑː"),
+    ("ˈɛs", "ˈɛs"),
+    ("tˈi", "tˈiː"),
+    ("jˈu", "jˈuː"),
+    ("vˈi", "vˈiː"),
+    ("dˈʌbəlju", "dˈʌbəljuː"),
+    ("ˈɛks", "ˈɛks"),
+    ("wˈaɪ", "wˈaɪ"),
+    ("zˈi", "zˈɛd"),
+];
+
+/// Spell out text, so that letters are read by their names and digits one by one, with short pauses between
+/// them. Letters are given as [pronunciation overrides](super::overrides), since a phonemizer would read a single
+/// "a" like in "a cat". Anything but letters and digits, like the dash in "ABC-123", is left out.
+pub fn spell(text: &str, language: Language) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| spell_char(c, language))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn spell_char(c: char, language: Language) -> String {
+    if let Some(digit) = c.to_digit(10) {
+        return cardinal(digit as u64, language);
+    }
+
+    let index = c.to_ascii_lowercase() as usize;
+    match c.is_ascii_alphabetic() {
+        true => {
+            let (american, british) = LETTERS[index - 'a' as usize];
+            let phonemes = match language {
+                Language::EnGb => british,
+                _ => american,
+            };
+            format!("[{c}](/{phonemes}/)")
+        }
+        false => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::overrides;
+
+    #[test]
+    fn spell_test() {
+        assert_eq!(
+            spell("AB-12", Language::EnUs),
+            "[A](/ˈeɪ/), [B](/bˈi/), one, two"
+        );
+        assert_eq!(spell("z0", Language::EnGb), "[z](/zˈɛd/), zero");
+        assert_eq!(spell("é", Language::EnUs), "é");
+        assert_eq!(spell(" - ", Language::EnUs), "");
+    }
+
+    #[test]
+    fn letters_are_valid_overrides_test() {
+        let spelled = spell("abcdefghijklmnopqrstuvwxyz", Language::EnGb)
+            + &spell("abcdefghijklmnopqrstuvwxyz", Language::EnUs);
+
+        assert!(overrides::spans(&spelled).is_ok());
+    }
+}
@@ -0,0 +1,266 @@
+//! Timings of the tokens and words of generated audio, e.g. to highlight words while they are spoken.
+
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::{
+    Language,
+    text::{
+        normalize::normalize,
+        overrides::{self, Span},
+        spell::spell,
+    },
+};
+
+/// Audio generated from phonemes, with the samples every token was spoken in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimedAudio {
+    pub audio: Vec<f32>,
+    /// The phonemes Kokoro read, i.e. after [`phonemes::to_kokoro`](crate::phonemes::to_kokoro) and without
+    /// symbols it doesn't know, with their samples. The silence around chunks belongs to no token.
+    pub tokens: Vec<TimedToken>,
+    /// Whether the samples of tokens were estimated within each chunk, as the model doesn't output the durations
+    /// it predicted. The samples of the chunks are exact either way.
+    pub estimated: bool,
+}
+
+/// A token and the range of samples it was spoken in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedToken {
+    pub phoneme: char,
+    pub samples: Range<usize>,
+}
+
+/// A word of a text and the range of samples it was spoken in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordTiming {
+    /// Byte range of the word in the text.
+    pub text: Range<usize>,
+    pub samples: Range<usize>,
+}
+
+impl TimedAudio {
+    /// Append the audio of a chunk, splitting its samples among the tokens proportionally to their weights. The
+    /// weights include the padding before and after the tokens.
+    pub(crate) fn push_chunk(&mut self, audio: Vec<f32>, tokens: &[char], weights: &[f32]) {
+        debug_assert_eq!(tokens.len() + 2, weights.len());
+
+        // Chunks are split at word boundaries, whose spaces are trimmed
+        let start = self.audio.len();
+        if !self.tokens.is_empty() {
+            self.tokens.push(TimedToken {
+                phoneme: ' ',
+                samples: start..start,
+            });
+        }
+
+        let total: f64 = weights.iter().map(|w| *w as f64).sum();
+        let len = audio.len() as f64;
+        let boundary = |weight: f64| match total > 0.0 {
+            true => start + (weight / total * len).round() as usize,
+            false => start,
+        };
+
+        let mut weight = weights[0] as f64;
+        for (phoneme, token_weight) in tokens.iter().zip(&weights[1..]) {
+            let token_start = boundary(weight);
+            weight += *token_weight as f64;
+            self.tokens.push(TimedToken {
+                phoneme: *phoneme,
+                samples: token_start..boundary(weight),
+            });
+        }
+        self.audio.extend(audio);
+    }
+
+    /// Samples of the words of the phonemes, which are separated by spaces. Punctuation isn't part of words.
+    pub fn words(&self) -> Vec<Range<usize>> {
+        let mut words = Vec::new();
+        let mut word: Option<Range<usize>> = None;
+
+        for token in &self.tokens {
+            if token.phoneme == ' ' {
+                words.extend(word.take());
+            } else if token.phoneme.is_alphabetic() {
+                word = Some(match word {
+                    Some(word) => word.start..token.samples.end,
+                    None => token.samples.clone(),
+                });
+            }
+        }
+        words.extend(word);
+
+        words
+    }
+}
+
+/// Byte ranges of the words of a text, which are separated by whitespace. Markup like `[New York](/nu jˈɔɹk/)`
+/// is a single word.
+pub fn word_spans(text: &str) -> Vec<Range<usize>> {
+    let markup = overrides::markup_ranges(text);
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        let separates = c.is_whitespace() && !markup.iter().any(|range| range.contains(&i));
+        match (separates, start) {
+            (true, Some(word_start)) => {
+                words.push(word_start..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    words.extend(start.map(|word_start| word_start..text.len()));
+
+    words
+}
+
+/// Time the words of a text with the audio generated from its phonemes. `spoken_words` tells how many words of
+/// phonemes a word became, e.g. two for "42", like [`spoken_words`] for text and [`count_words`] for phonemes.
+/// If these don't add up, because words were read differently in context, the words of phonemes are spread
+/// evenly over the words of the text. Words without phonemes, like a dash, are left out.
+pub fn align_words(
+    text: &str,
+    timed: &TimedAudio,
+    spoken_words: impl Fn(&str) -> Result<usize>,
+) -> Result<Vec<WordTiming>> {
+    let spoken = timed.words();
+    let words = word_spans(text);
+    let counts = words
+        .iter()
+        .map(|word| spoken_words(&text[word.clone()]))
+        .collect::<Result<Vec<usize>>>()?;
+    let total: usize = counts.iter().sum();
+    if spoken.is_empty() || total == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Index of the spoken word a count of words of the text corresponds to
+    let scale = |count: usize| (count * spoken.len() + total / 2) / total;
+
+    let mut timings = Vec::new();
+    let mut before = 0;
+    for (word, count) in words.into_iter().zip(counts) {
+        if count == 0 {
+            continue;
+        }
+
+        let first = scale(before).min(spoken.len() - 1);
+        let last = scale(before + count).max(first + 1) - 1;
+        timings.push(WordTiming {
+            text: word,
+            samples: spoken[first].start..spoken[last].end,
+        });
+        before += count;
+    }
+
+    Ok(timings)
+}
+
+/// Number of words a word of text is read as in a language, without phonemizing it: numbers and abbreviations
+/// count the words they are normalized into, overrides the words of their phonemes and spelled words their
+/// letters and digits.
+pub fn spoken_words(word: &str, language: Language) -> Result<usize> {
+    let mut count = 0;
+    for span in overrides::spans(word)? {
+        count += match span {
+            Span::Text(text) | Span::Emphasis { text, .. } => {
+                count_words(&normalize(text, language))
+            }
+            Span::Override { phonemes, .. } => count_words(phonemes),
+            Span::Spell(text) => spoken_words(&spell(text, language), language)?,
+        };
+    }
+
+    Ok(count)
+}
+
+/// Number of words of text or phonemes, which are separated by whitespace or hyphens, not counting groups of
+/// only punctuation.
+pub fn count_words(text: &str) -> usize {
+    text.split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timed_audio(chunks: &[&str]) -> TimedAudio {
+        let mut timed = TimedAudio::default();
+        for chunk in chunks {
+            let tokens: Vec<char> = chunk.chars().collect();
+            let mut weights = vec![1.0; tokens.len() + 2];
+            weights[0] = 2.0;
+            *weights.last_mut().unwrap() = 2.0;
+            timed.push_chunk(vec![0.0; 100 * (tokens.len() + 4)], &tokens, &weights);
+        }
+        timed
+    }
+
+    #[test]
+    fn push_chunk_test() {
+        let timed = timed_audio(&["ab c.", "d"]);
+
+        assert_eq!(timed.audio.len(), 900 + 500);
+        assert_eq!(timed.tokens[0].samples, 200..300);
+        assert_eq!(timed.tokens[3].samples, 500..600);
+        // The word boundary between chunks
+        assert_eq!(timed.tokens[5].phoneme, ' ');
+        assert_eq!(timed.tokens[6].samples, 900 + 200..900 + 300);
+
+        assert_eq!(timed.words(), vec![200..400, 500..600, 1100..1200]);
+    }
+
+    #[test]
+    fn word_spans_test() {
+        let text = " Say [New York](/nu jˈɔɹk/), \n [ABC](spell)!";
+        let words: Vec<&str> = word_spans(text).into_iter().map(|w| &text[w]).collect();
+
+        assert_eq!(words, ["Say", "[New York](/nu jˈɔɹk/),", "[ABC](spell)!"]);
+        assert!(word_spans("  ").is_empty());
+    }
+
+    #[test]
+    fn align_words_test() -> Result<()> {
+        let text = "I am 42 — now.";
+        let timed = timed_audio(&["I ˈæm fˈɔɹɾi tˈu — nˈW."]);
+        let words = timed.words();
+
+        let aligned = align_words(text, &timed, |word| spoken_words(word, Language::EnUs))?;
+        let texts: Vec<&str> = aligned.iter().map(|w| &text[w.text.clone()]).collect();
+        assert_eq!(texts, ["I", "am", "42", "now."]);
+        assert_eq!(aligned[2].samples, words[2].start..words[3].end);
+        assert_eq!(aligned[3].samples, words[4]);
+
+        // Phonemes read as fewer words than the text are spread over it
+        let timed = timed_audio(&["ˈIæm"]);
+        let aligned = align_words("I am", &timed, |word| Ok(count_words(word)))?;
+        assert_eq!(aligned.len(), 2);
+        assert!(aligned.iter().all(|w| w.samples == timed.words()[0]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spoken_words_test() -> Result<()> {
+        let cases = [
+            ("now.", 1),
+            ("42", 2),
+            ("—", 0),
+            ("[New York](/nu jˈɔɹk/),", 2),
+            ("[AB2](spell)", 3),
+            ("[really](++)", 1),
+        ];
+
+        for (word, expected) in cases {
+            assert_eq!(spoken_words(word, Language::EnUs)?, expected, "{word:?}");
+        }
+
+        Ok(())
+    }
+}
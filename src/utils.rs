@@ -1,22 +1,48 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use anyhow::Result;
 
+use crate::SAMPLE_RATE;
+
 pub fn write_to_wav<P: AsRef<Path>>(samples: Vec<f32>, path: P) -> Result<()> {
+    write_wav(samples, 1, path)
+}
+
+/// Write stereo samples, given as `[left, right]` pairs, to a WAV file.
+pub fn write_stereo_to_wav<P: AsRef<Path>>(samples: Vec<[f32; 2]>, path: P) -> Result<()> {
+    write_wav(samples.into_iter().flatten(), 2, path)
+}
+
+/// Write interleaved samples with the given number of channels to a WAV file.
+fn write_wav<P: AsRef<Path>>(
+    samples: impl IntoIterator<Item = f32>,
+    channels: u16,
+    path: P,
+) -> Result<()> {
     // metadata
     let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 24000,
+        channels,
+        sample_rate: SAMPLE_RATE,
         sample_format: hound::SampleFormat::Float,
         bits_per_sample: 32,
     };
 
     // write samples
     let mut wav_writer = hound::WavWriter::create(path, spec)?;
-    for &sample in samples.iter() {
+    for sample in samples {
         wav_writer.write_sample(sample)?;
     }
     wav_writer.finalize().unwrap();
 
     Ok(())
 }
+
+/// Generate silence of the given duration in seconds.
+pub fn silence(seconds: f32) -> Vec<f32> {
+    vec![0f32; (seconds * SAMPLE_RATE as f32).round() as usize]
+}
+
+/// Duration of the given number of samples.
+pub fn samples_to_duration(samples: usize) -> Duration {
+    Duration::from_secs_f64(samples as f64 / SAMPLE_RATE as f64)
+}